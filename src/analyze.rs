@@ -1,24 +1,50 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::vec::Vec;
 
+use clang::diagnostic::Severity;
 use clang::source::{File, SourceRange};
-use clang::{Entity, EntityKind, EntityVisitResult, Index};
+use clang::{
+    Entity, EntityKind, EntityVisitResult, Index, TranslationUnit, Type, TypeKind, Unsaved,
+};
+use serde::Serialize;
 
 mod includes;
 use includes::{FileID, IncludeGraph};
+mod pragmas;
+use pragmas::{FilePragmas, Pragma};
+
+use super::config::Settings;
+use super::error::{Error, Result};
+use super::fileio::{self, IncludeStatement};
+use super::mappings::Mappings;
+use super::stdlib;
+use super::util;
 
 // Connect to clang library
 lazy_static::lazy_static! {
     static ref CLANG: clang::Clang = clang::Clang::new().expect("libclang loading failed");
 }
 
+/// Distinguishes the precompiled headers of all threads
+static PRECOMPILED_ID: AtomicUsize = AtomicUsize::new(0);
+
 trait EntityExt<'tu> {
     /// Returns the corresponding sourcefile
     fn get_sourcefile(&self) -> Option<File>;
 
     /// Returns the remaining line after this entity
     fn get_remaining_line(&self) -> Option<String>;
+
+    /// Returns the whole line where this entity starts
+    fn get_line(&self) -> Option<String>;
 }
 
 impl<'tu> EntityExt<'tu> for Entity<'tu> {
@@ -43,50 +69,142 @@ impl<'tu> EntityExt<'tu> for Entity<'tu> {
         }
         None
     }
+
+    fn get_line(&self) -> Option<String> {
+        let start = self.get_range()?.get_start().get_file_location();
+        let content = start.file?.get_contents()?;
+        let offset = (start.offset as usize).min(content.len());
+        let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[offset..]
+            .find('\n')
+            .map_or(content.len(), |i| offset + i);
+        Some(content[line_start..line_end].into())
+    }
+}
+
+/// Reason why an include is never reported as unused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IgnoreReason {
+    /// Explicitly marked with `// keep` or `// IWYU pragma: keep`
+    KeepComment,
+    /// Provided to the including files with `// IWYU pragma: export`
+    Exported,
+    /// Matched by the `ignore_includes` regex
+    IgnoreIncludes,
+    /// Corresponding header of the sourcefile
+    AssociatedHeader,
+    /// Computed include (`#include MACRO`), whose target depends on the build configuration
+    Computed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IgnoredInclude {
+    pub include: Include,
+    pub reason: IgnoreReason,
 }
 
 /// Checks if this include should be ignored
 fn include_should_be_ignored(
     entity: &Entity,
-    ignore_includes: &regex::Regex,
+    settings: &Settings,
     from: &File,
     to: &File,
-) -> bool {
-    lazy_static::lazy_static! {
-        static ref RE_KEEP: regex::Regex = regex::Regex::new("^[ \\t]*//[ \\t]*keep").unwrap();
-    }
-
+    exported: bool,
+) -> Option<IgnoreReason> {
     if entity.is_in_main_file() {
+        if entity
+            .get_line()
+            .is_some_and(|line| fileio::is_computed_include(&line))
+        {
+            return Some(IgnoreReason::Computed);
+        }
         // Ignore explicitly marked includes `// keep`
         if let Some(line_end) = entity.get_remaining_line() {
-            if RE_KEEP.is_match(&line_end)
-                || ignore_includes.is_match(&to.get_path().to_string_lossy())
+            let reason = if settings.keep_comment.is_match(&line_end)
+                || Pragma::parse(&line_end) == Some(Pragma::Keep)
+            {
+                Some(IgnoreReason::KeepComment)
+            } else if exported {
+                Some(IgnoreReason::Exported)
+            } else if settings
+                .ignore_includes
+                .is_match(&to.get_path().to_string_lossy())
             {
-                println!(
+                Some(IgnoreReason::IgnoreIncludes)
+            } else {
+                None
+            };
+            if reason.is_some() {
+                info!(
                     "{}: ignore {}",
                     from.get_path().to_string_lossy(),
                     entity.get_name().unwrap()
                 );
-                return true;
+                return reason;
             }
         }
         // Ignore corresponding headers in sourcefiles
-        from.get_path().file_stem() == to.get_path().file_stem()
-    } else {
-        false
+        if settings.is_associated_header(&from.get_path(), &to.get_path()) {
+            return Some(IgnoreReason::AssociatedHeader);
+        }
     }
+    None
+}
+
+/// Returns the pragmas of the file, parsing them if necessary
+fn file_pragmas<'a>(pragmas: &'a mut HashMap<FileID, FilePragmas>, file: &File) -> &'a FilePragmas {
+    pragmas
+        .entry(file.get_id())
+        .or_insert_with(|| FilePragmas::parse(&file.get_contents().unwrap_or_default()))
 }
 
 /// Create the include graph
+///
+/// Includes exported with IWYU pragmas are provided by the including file.
 fn find_includes(
     entity: Entity,
-    ignore_includes: &regex::Regex,
+    settings: &Settings,
     includes: &mut IncludeGraph,
+    ignored: &mut Vec<IgnoredInclude>,
+    names: &mut HashMap<FileID, (String, PathBuf)>,
+    pragmas: &mut HashMap<FileID, FilePragmas>,
 ) -> EntityVisitResult {
     if entity.get_kind() == EntityKind::InclusionDirective {
         if let Some(from) = entity.get_sourcefile() {
             if let Some(to) = entity.get_file() {
-                if !include_should_be_ignored(&entity, ignore_includes, &from, &to) {
+                if let Some(name) = entity.get_name() {
+                    names
+                        .entry(to.get_id())
+                        .or_insert_with(|| (name, to.get_path()));
+                }
+                file_pragmas(pragmas, &to);
+
+                let start = entity
+                    .get_range()
+                    .map(|r| r.get_start().get_file_location());
+                let exported = entity
+                    .get_remaining_line()
+                    .and_then(|line| Pragma::parse(&line))
+                    == Some(Pragma::Export)
+                    || file_pragmas(pragmas, &from)
+                        .is_exported(start.map_or(0, |s| s.line as usize));
+
+                if let Some(reason) =
+                    include_should_be_ignored(&entity, settings, &from, &to, exported)
+                {
+                    let include = Include::new(
+                        entity.get_name().unwrap(),
+                        to.get_path(),
+                        start.map_or(0, |s| s.line as usize),
+                    );
+                    ignored.push(IgnoredInclude {
+                        include: include.with_column(start.map_or(1, |s| s.column as usize)),
+                        reason,
+                    });
+                } else if exported {
+                    includes.insert_export(from.get_id(), to.get_id());
+                } else {
                     includes.insert(from.get_id(), to.get_id());
                 }
             }
@@ -96,85 +214,196 @@ fn find_includes(
     EntityVisitResult::Continue
 }
 
-fn mark_includes_impl(entity: Entity, includes: &mut IncludeGraph) {
+/// Returns the declaration referenced by this entity, preferring definitions (if existing)
+fn get_referenced<'tu>(entity: &Entity<'tu>) -> Option<Entity<'tu>> {
     match entity.get_kind() {
         EntityKind::DeclRefExpr
         | EntityKind::TypeRef
         | EntityKind::TemplateRef
-        | EntityKind::MacroExpansion => {
-            // Prefer definitions over declarations (if existing)
-            if let Some(reference) = entity.get_definition().or_else(|| entity.get_reference()) {
-                if reference == entity {
-                    return;
-                }
-                if !reference.is_in_main_file() {
-                    if let Some(to) = reference.get_sourcefile() {
-                        includes.mark_used(&to.get_id());
-                    }
-                }
-                mark_includes_impl(reference, includes)
+        | EntityKind::MacroExpansion
+        | EntityKind::MemberRefExpr
+        | EntityKind::MemberRef
+        | EntityKind::VariableRef => entity.get_definition().or_else(|| entity.get_reference()),
+        // The first candidate, the others are marked separately
+        EntityKind::OverloadedDeclRef => entity
+            .get_overloaded_declarations()
+            .and_then(|overloads| overloads.into_iter().next()),
+        // Canonical, as the base might be named by `decltype`
+        EntityKind::BaseSpecifier => entity
+            .get_type()
+            .and_then(|base| base.get_canonical_type().get_declaration())
+            .map(|base| base.get_definition().unwrap_or(base)),
+        EntityKind::TypeAliasDecl | EntityKind::TypedefDecl => entity
+            .get_typedef_underlying_type()
+            .and_then(|typeref| typeref.get_declaration()),
+        _ => None,
+    }
+}
+
+/// Returns the declaration of the type of this entity, if its definition is required.
+///
+/// This covers types that are not referenced by a `TypeRef`,
+/// like `auto`, `decltype`, return values and implicit constructor calls.
+fn get_type_declaration<'tu>(entity: &Entity<'tu>) -> Option<Entity<'tu>> {
+    let ty = match entity.get_kind() {
+        EntityKind::VarDecl | EntityKind::FieldDecl | EntityKind::CallExpr => entity.get_type(),
+        EntityKind::FunctionDecl | EntityKind::Method if entity.is_definition() => {
+            entity.get_result_type()
+        }
+        _ => None,
+    }?;
+    // Pointers and references have no declaration
+    let declaration = ty.get_canonical_type().get_declaration()?;
+    Some(declaration.get_definition().unwrap_or(declaration))
+}
+
+/// Visits the declarations in other files that are (transitively) referenced by this entity
+fn visit_references<'tu, F: FnMut(Entity<'tu>)>(entity: Entity<'tu>, visitor: &mut F) {
+    if entity.get_kind() == EntityKind::OverloadedDeclRef {
+        // Any of the overloads might be selected on instantiation
+        for overload in entity.get_overloaded_declarations().unwrap_or_default() {
+            if !overload.is_in_main_file() {
+                visitor(overload);
             }
         }
-        EntityKind::TypeAliasDecl | EntityKind::TypedefDecl => {
-            if let Some(typeref) = entity.get_typedef_underlying_type() {
-                if let Some(declaration) = typeref.get_declaration() {
-                    if declaration == entity {
-                        return;
-                    }
-                    if !declaration.is_in_main_file() {
-                        if let Some(to) = declaration.get_sourcefile() {
-                            includes.mark_used(&to.get_id());
-                        }
-                    }
-                    mark_includes_impl(declaration, includes)
-                }
+    }
+    if let Some(reference) = get_referenced(&entity) {
+        if reference == entity {
+            return;
+        }
+        if !reference.is_in_main_file() {
+            visitor(reference);
+        }
+        visit_references(reference, visitor)
+    }
+}
+
+/// Collects the files opening the namespaces below the `entity`, by their USR
+fn namespace_files(entity: Entity, result: &mut HashMap<String, HashSet<FileID>>) {
+    for child in entity.get_children() {
+        if child.get_kind() == EntityKind::Namespace {
+            if let (Some(usr), Some(file)) = (child.get_usr(), child.get_sourcefile()) {
+                result.entry(usr.0).or_default().insert(file.get_id());
             }
+            namespace_files(child, result);
         }
-        _ => {}
     }
 }
 
-/// Marks all necessary includes
-fn mark_includes(entity: Entity, includes: &mut IncludeGraph) -> EntityVisitResult {
-    if !entity.is_in_main_file() {
-        return EntityVisitResult::Continue;
+/// Returns the file providing the declaration.
+///
+/// Standard library symbols are provided by their standard header, if it is included.
+fn providing_file(
+    declaration: &Entity,
+    names: &HashMap<FileID, (String, PathBuf)>,
+) -> Option<FileID> {
+    stdlib::symbol_header(declaration)
+        .and_then(|header| find_file(names, header))
+        .or_else(|| declaration.get_sourcefile().map(|f| f.get_id()))
+}
+
+fn mark_includes_impl(
+    entity: Entity,
+    includes: &mut IncludeGraph,
+    names: &HashMap<FileID, (String, PathBuf)>,
+) {
+    visit_references(entity, &mut |declaration| {
+        if let Some(to) = providing_file(&declaration, names) {
+            includes.mark_used(&to);
+        }
+    });
+}
+
+/// Returns whether the `TypeRef` below the `parent` requires the complete type.
+///
+/// Pointers and references, as well as the parameter and return types
+/// of function declarations only need incomplete types.
+fn requires_complete_type(parent: &Entity) -> bool {
+    let indirect = |ty: Option<Type>| {
+        matches!(
+            ty.map(|t| t.get_kind()),
+            Some(TypeKind::Pointer | TypeKind::LValueReference | TypeKind::RValueReference)
+        )
+    };
+    match parent.get_kind() {
+        EntityKind::VarDecl | EntityKind::FieldDecl => !indirect(parent.get_type()),
+        EntityKind::ParmDecl => {
+            !indirect(parent.get_type())
+                && parent
+                    .get_semantic_parent()
+                    .is_none_or(|function| function.is_definition())
+        }
+        EntityKind::FunctionDecl | EntityKind::Method => {
+            !indirect(parent.get_result_type()) && parent.is_definition()
+        }
+        _ => true,
     }
+}
 
-    mark_includes_impl(entity, includes);
+/// Returns the forward declaration of the class, nested in its namespaces
+fn forward_declaration(declaration: &Entity) -> Option<String> {
+    let keyword = match declaration.get_kind() {
+        EntityKind::ClassDecl => "class",
+        EntityKind::StructDecl => "struct",
+        EntityKind::UnionDecl => "union",
+        _ => return None,
+    };
+    let mut result = format!("{} {};", keyword, declaration.get_name()?);
 
-    EntityVisitResult::Recurse
+    let mut parent = declaration.get_semantic_parent();
+    while let Some(entity) = parent {
+        match entity.get_kind() {
+            EntityKind::TranslationUnit => break,
+            EntityKind::Namespace => {
+                result = format!("namespace {} {{ {} }}", entity.get_name()?, result)
+            }
+            // Nested classes cannot be forward declared
+            _ => return None,
+        }
+        parent = entity.get_semantic_parent();
+    }
+    Some(result)
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Include {
     pub name: String,
     pub path: PathBuf,
     pub line: usize,
+    /// Column of the `#`
+    pub column: usize,
 }
 
 impl Include {
     pub fn new(name: String, path: PathBuf, line: usize) -> Include {
-        Include { name, path, line }
+        Include {
+            name,
+            path,
+            line,
+            column: 1,
+        }
+    }
+
+    pub fn with_column(mut self, column: usize) -> Include {
+        self.column = column;
+        self
     }
 
     /// Returns the include path relative to the file if possible
-    pub fn get_local(&self, file: &Path, include_paths: &[PathBuf]) -> Option<String> {
+    pub fn get_local(
+        &self,
+        file: &Path,
+        include_paths: &[PathBuf],
+        source_roots: &[String],
+    ) -> Option<String> {
         // Prefer relative includes if possible
-        // Allow relative includes from /src/ and /include/
+        // Allow relative includes from the source roots (e.g. /src/ and /include/)
         if let Some(filedir) = file.parent() {
-            for ancestor in filedir.ancestors() {
-                if ancestor.ends_with("src")
-                    || ancestor.ends_with("include")
-                    || ancestor.ends_with("src/main")
-                    || ancestor.ends_with("include/main")
-                {
-                    if let Ok(file_relpath) = filedir.strip_prefix(ancestor) {
-                        for include_path in include_paths {
-                            let path: PathBuf = [include_path, file_relpath].iter().collect();
-                            if let Ok(relpath) = self.path.strip_prefix(path) {
-                                return Some(relpath.to_string_lossy().into());
-                            }
-                        }
+            for file_relpath in util::source_relpaths(filedir, source_roots) {
+                for include_path in include_paths {
+                    let path: PathBuf = [include_path, file_relpath].iter().collect();
+                    if let Ok(relpath) = self.path.strip_prefix(path) {
+                        return Some(relpath.to_string_lossy().into());
                     }
                 }
             }
@@ -189,74 +418,1298 @@ impl Include {
 
         None
     }
+
+    /// Returns how the `file` would include this header.
+    ///
+    /// Headers next to the file are included relative to it,
+    /// otherwise the name is derived from the include paths (see `get_local`).
+    /// Both are local includes.
+    /// Falls back to a global include of the name under which the header has been included.
+    pub fn statement(
+        &self,
+        file: &Path,
+        include_paths: &[PathBuf],
+        source_roots: &[String],
+    ) -> IncludeStatement {
+        // Clang keeps the paths as they were included (e.g. `dir/../Header.hpp`)
+        let include = Include {
+            path: self
+                .path
+                .canonicalize()
+                .unwrap_or_else(|_| self.path.clone()),
+            ..self.clone()
+        };
+        file.parent()
+            .and_then(|dir| include.path.strip_prefix(dir).ok())
+            .map(|relpath| relpath.to_string_lossy().into())
+            .or_else(|| include.get_local(file, include_paths, source_roots))
+            .map_or_else(
+                || IncludeStatement::Global(self.name.clone()),
+                IncludeStatement::Local,
+            )
+    }
 }
 
-/// Locate the unused includes in the sourcefile
-fn collect_unused_includes(
+/// Collect the includes of the sourcefile
+fn collect_direct_includes(
     entity: Entity,
     source_range: SourceRange,
-    unused: &HashSet<&FileID>,
-    result: &mut Vec<Include>,
+    result: &mut Vec<(FileID, Include)>,
 ) {
     if let Some(file) = entity.get_file() {
-        let path = file.get_path();
-        if unused.contains(&&file.get_id()) {
-            let start = source_range.get_start().get_file_location();
-            result.push(Include::new(
+        let start = source_range.get_start().get_file_location();
+        result.push((
+            file.get_id(),
+            Include::new(
                 entity.get_name().unwrap(),
-                path,
+                file.get_path(),
                 start.line as usize,
-            ));
+            )
+            .with_column(start.column as usize),
+        ));
+    }
+}
+
+/// Diagnostic reported by clang while parsing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: String,
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(diag: &clang::diagnostic::Diagnostic) -> Diagnostic {
+        let location = diag.get_location().get_file_location();
+        Diagnostic {
+            severity: format!("{:?}", diag.get_severity()).to_lowercase(),
+            path: location.file.map(|f| f.get_path()),
+            line: location.line as usize,
+            column: location.column as usize,
+            message: diag.get_text(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(
+                f,
+                "{}:{}:{}: ",
+                path.to_string_lossy(),
+                self.line,
+                self.column
+            )?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Header declaring symbols that are used by the sourcefile, but is only included indirectly
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingInclude {
+    /// The header with the location of the first use in the sourcefile
+    pub include: Include,
+    /// How the sourcefile would include the header
+    #[serde(skip)]
+    pub statement: IncludeStatement,
+    /// The used symbols declared in the header
+    pub symbols: Vec<String>,
+}
+
+/// Returns the included file with the given name.
+///
+/// Files included under exactly this name are preferred over files whose path only ends
+/// with it, ties are broken by the path to be independent of the map order.
+fn find_file(names: &HashMap<FileID, (String, PathBuf)>, include: &str) -> Option<FileID> {
+    names
+        .iter()
+        .filter(|(_, (name, path))| name == include || path.ends_with(include))
+        .min_by_key(|(_, (name, path))| (name != include, path))
+        .map(|(id, _)| *id)
+}
+
+/// Public headers provide the symbols of the private headers they are mapped to
+fn insert_mappings(
+    includes: &mut IncludeGraph,
+    names: &HashMap<FileID, (String, PathBuf)>,
+    mappings: &Mappings,
+) {
+    for (private, (_, path)) in names {
+        if let Some(public) = mappings.public(path) {
+            if let Some(public) = find_file(names, public.path()) {
+                if public != *private {
+                    includes.insert_export(public, *private);
+                }
+            }
+        }
+    }
+}
+
+/// Collect the headers that are used but not directly included.
+///
+/// Headers that are exported by direct includes are already `provided`.
+/// Private headers are replaced by their public header or never suggested.
+fn collect_missing_includes(
+    uses: &[(Entity, Entity)],
+    provided: &HashSet<FileID>,
+    names: &HashMap<FileID, (String, PathBuf)>,
+    pragmas: &HashMap<FileID, FilePragmas>,
+    no_include: &[String],
+    settings: &Settings,
+) -> Vec<MissingInclude> {
+    let mut result: Vec<MissingInclude> = Vec::new();
+    let mut indices = HashMap::new();
+
+    for (entity, reference) in uses {
+        if let Some(file) = reference.get_sourcefile() {
+            let mut id = file.get_id();
+            // Standard library symbols and private headers are replaced by their public header
+            let public = if let Some(header) = stdlib::symbol_header(reference) {
+                Some(Some(header))
+            } else {
+                match pragmas.get(&id).and_then(|p| p.private.as_ref()) {
+                    Some(public) => Some(public.as_deref()),
+                    None => settings
+                        .mappings
+                        .public(&file.get_path())
+                        .map(|public| Some(public.path())),
+                }
+            };
+            if let Some(public) = public {
+                match public.and_then(|public| find_file(names, public)) {
+                    Some(public) => id = public,
+                    None => continue,
+                }
+            }
+            if provided.contains(&id) {
+                continue;
+            }
+            let (name, path) = match names.get(&id) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            // Never suggest private headers
+            if settings.ignore_includes.is_match(&path.to_string_lossy())
+                || no_include.contains(name)
+            {
+                continue;
+            }
+            let symbol = reference
+                .get_name()
+                .or_else(|| entity.get_display_name())
+                .unwrap_or_default();
+
+            if let Some(&i) = indices.get(&id) {
+                let missing: &mut MissingInclude = &mut result[i];
+                if !missing.symbols.contains(&symbol) {
+                    missing.symbols.push(symbol);
+                }
+            } else {
+                let start = entity
+                    .get_range()
+                    .map(|r| r.get_start().get_file_location());
+                let include = Include::new(
+                    name.clone(),
+                    path.clone(),
+                    start.map_or(0, |s| s.line as usize),
+                )
+                .with_column(start.map_or(1, |s| s.column as usize));
+
+                indices.insert(id, result.len());
+                result.push(MissingInclude {
+                    statement: IncludeStatement::Global(include.name.clone()),
+                    include,
+                    symbols: vec![symbol],
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Names the `missing` includes like the `filepath` would include them.
+///
+/// The names collected from the include directives are those of the first includer.
+fn respell_includes(
+    missing: &mut [MissingInclude],
+    filepath: &Path,
+    args: &[String],
+    settings: &Settings,
+) {
+    let include_paths = util::include_paths(&args.join(" "))
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    for missing in missing {
+        missing.statement =
+            missing
+                .include
+                .statement(filepath, &include_paths, &settings.source_roots);
+        missing.include.name = missing.statement.path().into();
+    }
+}
+
+/// Include that is only used for incomplete types and can be replaced by forward declarations
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardDeclarable {
+    pub include: Include,
+    /// Forward declarations of the used classes
+    pub declarations: Vec<String>,
+}
+
+/// Collect the includes that are only required for incomplete types
+fn collect_forward_declarations(
+    direct: &[(FileID, Include)],
+    unused: &HashSet<&FileID>,
+    unused_complete: &HashSet<&FileID>,
+    incomplete: &[(Entity, String)],
+) -> Vec<ForwardDeclarable> {
+    let mut result = Vec::new();
+    for (id, include) in direct {
+        if unused.contains(id) || !unused_complete.contains(id) {
+            continue;
+        }
+        let mut declarations = Vec::new();
+        for (declaration, forward) in incomplete {
+            if declaration.get_sourcefile().map(|f| f.get_id()) == Some(*id)
+                && !declarations.contains(forward)
+            {
+                declarations.push(forward.clone());
+            }
+        }
+        if !declarations.is_empty() {
+            result.push(ForwardDeclarable {
+                include: include.clone(),
+                declarations,
+            });
+        }
+    }
+    result
+}
+
+/// Results of the analysis of a single sourcefile
+#[derive(Debug, Default, Serialize)]
+pub struct Analysis {
+    /// Includes that are not referenced
+    pub unused: Vec<Include>,
+    /// Includes of the sourcefile that are never reported as unused
+    pub ignored: Vec<IgnoredInclude>,
+    /// Headers that should be included directly
+    pub missing: Vec<MissingInclude>,
+    /// Includes that can be replaced by forward declarations
+    pub forward: Vec<ForwardDeclarable>,
+    /// Associated header that is not the first include
+    pub associated_not_first: Option<Include>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    /// Only keeps the findings that are also found in the `other` build configuration.
+    ///
+    /// The diagnostics of both configurations are kept.
+    pub fn intersect(&mut self, other: Analysis) {
+        self.unused.retain(|include| {
+            other
+                .unused
+                .iter()
+                .any(|o| o.line == include.line && o.path == include.path)
+        });
+        self.missing.retain(|missing| {
+            other
+                .missing
+                .iter()
+                .any(|o| o.include.path == missing.include.path)
+        });
+        self.forward.retain(|forward| {
+            other.forward.iter().any(|o| {
+                o.include.line == forward.include.line && o.declarations == forward.declarations
+            })
+        });
+        if other.associated_not_first.is_none() {
+            self.associated_not_first = None;
+        }
+        for diagnostic in other.diagnostics {
+            if !self.diagnostics.contains(&diagnostic) {
+                self.diagnostics.push(diagnostic);
+            }
         }
     }
 }
 
+/// Analyzes the sourcefile with the compiler args of all build `configurations`.
+///
+/// Only the findings of all configurations are reported.
+pub fn analyze_configurations(
+    filepath: &Path,
+    configurations: &[Vec<String>],
+    settings: &Settings,
+    unsaved: &[Unsaved],
+) -> Result<Analysis> {
+    let index = create_index();
+    let mut units = TranslationUnits::new(&index, 1);
+    units.analyze_configurations(filepath, configurations, settings, unsaved, None)
+}
+
+/// Creates a new libclang index for the [`TranslationUnits`]
+pub fn create_index() -> Index<'static> {
+    Index::new(&CLANG, false, true)
+}
+
+/// Numbers of the translation units parsed by [`TranslationUnits`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ParseStatistics {
+    /// Sourcefiles parsed without a precompiled header
+    pub parses: usize,
+    /// Precompiled headers that were built or rebuilt
+    pub precompiled: usize,
+    /// Sourcefiles parsed on top of a precompiled header
+    pub reused: usize,
+}
+
+/// Header that is parsed once and saved as precompiled header for the files including it
+struct Precompiled<'i> {
+    /// The header unit, reparsed if the header or one of its includes was modified
+    unit: TranslationUnit<'i>,
+    /// The saved AST file
+    path: PathBuf,
+    /// Files of the header unit and their versions when the unit was parsed
+    inputs: Vec<(PathBuf, usize)>,
+    /// Macros tested by the conditions of the files of the header unit
+    conditions: HashSet<String>,
+}
+
+impl<'i> Precompiled<'i> {
+    /// Returns whether none of the files of the header unit was modified since it was parsed
+    fn is_current(&self, versions: &HashMap<PathBuf, usize>) -> bool {
+        self.inputs
+            .iter()
+            .all(|(path, version)| versions.get(path).copied().unwrap_or(0) == *version)
+    }
+
+    /// Returns whether the sourcefile defines macros that are tested by the header unit,
+    /// whose conditions might then differ from the precompiled ones
+    fn is_redefined(&self, tu: &TranslationUnit) -> bool {
+        tu.get_entity().get_children().into_iter().any(|entity| {
+            entity.get_kind() == EntityKind::MacroDefinition
+                && entity
+                    .get_name()
+                    .is_some_and(|name| self.conditions.contains(&name))
+                && entity.get_sourcefile().is_some_and(|file| {
+                    let path = file.get_path();
+                    let path = path.canonicalize().unwrap_or(path);
+                    !self.inputs.iter().any(|(input, _)| *input == path)
+                })
+        })
+    }
+}
+
+/// Parses sourcefiles on top of the precompiled header they are analyzed for.
+///
+/// The files including a header are analyzed one after another, so only the first one
+/// parses the header and the others reuse it.
+/// The header units are kept and reparsed if their files were modified.
+/// The units are bound to the thread of their index.
+pub struct TranslationUnits<'i> {
+    index: &'i Index<'i>,
+    /// Precompiled headers by the header and its compiler args,
+    /// `None` if the header could not be precompiled
+    headers: HashMap<(PathBuf, Vec<String>), Option<Precompiled<'i>>>,
+    /// Keys in the order of their last use, the least recently used headers are dropped first
+    order: VecDeque<(PathBuf, Vec<String>)>,
+    capacity: usize,
+    statistics: ParseStatistics,
+}
+
+impl<'i> TranslationUnits<'i> {
+    /// Creates a cache that keeps at most `capacity` precompiled headers
+    pub fn new(index: &'i Index<'i>, capacity: usize) -> TranslationUnits<'i> {
+        TranslationUnits {
+            index,
+            headers: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+            statistics: ParseStatistics::default(),
+        }
+    }
+
+    /// Returns the numbers of the parsed units
+    pub fn statistics(&self) -> ParseStatistics {
+        self.statistics
+    }
+
+    /// Returns the precompiled `header`, building it if it is not cached yet
+    /// and rebuilding it if one of its files was modified.
+    fn precompiled(
+        &mut self,
+        header: &Path,
+        args: &[String],
+        unsaved: &[Unsaved],
+        versions: &HashMap<PathBuf, usize>,
+    ) -> Option<&Precompiled<'i>> {
+        let key = (header.to_path_buf(), args.to_vec());
+        let precompiled = match self.headers.remove(&key) {
+            Some(Some(precompiled)) if precompiled.is_current(versions) => Some(precompiled),
+            Some(Some(precompiled)) => {
+                let Precompiled { unit, path, .. } = precompiled;
+                match unit.reparse(unsaved) {
+                    Ok(unit) => self.save(unit, header, path, versions),
+                    Err(_) => {
+                        let _ = fs::remove_file(&path);
+                        None
+                    }
+                }
+            }
+            Some(None) => None,
+            None => {
+                if self.order.len() >= self.capacity {
+                    if let Some(Some(oldest)) =
+                        self.order.pop_front().and_then(|k| self.headers.remove(&k))
+                    {
+                        let _ = fs::remove_file(&oldest.path);
+                    }
+                }
+                let mut header_args = args.to_vec();
+                let language = header_language(header, args, None);
+                header_args.extend(["-x".to_string(), language.to_string()]);
+                self.index
+                    .parser(header)
+                    .arguments(&header_args)
+                    .unsaved(unsaved)
+                    .detailed_preprocessing_record(true)
+                    .incomplete(true)
+                    .parse()
+                    .ok()
+                    .and_then(|unit| {
+                        let id = PRECOMPILED_ID.fetch_add(1, Ordering::Relaxed);
+                        let path = env::temp_dir().join(format!(
+                            "unused-includes-{}-{}.pch",
+                            process::id(),
+                            id
+                        ));
+                        self.save(unit, header, path, versions)
+                    })
+            }
+        };
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key.clone());
+        self.headers.entry(key).or_insert(precompiled).as_ref()
+    }
+
+    /// Saves the header `unit` as precompiled header, if it has no errors
+    fn save(
+        &mut self,
+        unit: TranslationUnit<'i>,
+        header: &Path,
+        path: PathBuf,
+        versions: &HashMap<PathBuf, usize>,
+    ) -> Option<Precompiled<'i>> {
+        if has_errors(&unit) || unit.save(&path).is_err() {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        self.statistics.precompiled += 1;
+
+        let mut files = unit
+            .get_entity()
+            .get_children()
+            .into_iter()
+            .filter(|e| e.get_kind() == EntityKind::InclusionDirective)
+            .filter_map(|e| e.get_file())
+            .map(|f| f.get_path())
+            .chain([header.to_path_buf()])
+            .map(|path| path.canonicalize().unwrap_or(path))
+            .collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+
+        let conditions = files
+            .iter()
+            .filter_map(|file| fs::read_to_string(file).ok())
+            .flat_map(|content| fileio::conditional_macros(&content).into_keys())
+            .collect();
+        let inputs = files
+            .into_iter()
+            .map(|file| {
+                let version = versions.get(&file).copied().unwrap_or(0);
+                (file, version)
+            })
+            .collect();
+        Some(Precompiled {
+            unit,
+            path,
+            inputs,
+            conditions,
+        })
+    }
+
+    /// Parses the sourcefile, on top of the precompiled `header` if possible
+    fn parse(
+        &mut self,
+        filepath: &Path,
+        args: &[String],
+        unsaved: &[Unsaved],
+        header: Option<(&Path, &HashMap<PathBuf, usize>)>,
+    ) -> Result<TranslationUnit<'i>> {
+        let index = self.index;
+        if let Some((header, versions)) = header {
+            let tu = self
+                .precompiled(header, args, unsaved, versions)
+                .and_then(|precompiled| {
+                    let mut pch_args = args.to_vec();
+                    pch_args.extend([
+                        "-include-pch".to_string(),
+                        precompiled.path.to_string_lossy().into(),
+                    ]);
+                    let tu = index
+                        .parser(filepath)
+                        .arguments(&pch_args)
+                        .unsaved(unsaved)
+                        .detailed_preprocessing_record(true)
+                        .parse()
+                        .ok()?;
+                    // The header might not compile in front of the sourcefile
+                    (!has_errors(&tu) && !precompiled.is_redefined(&tu)).then_some(tu)
+                });
+            if let Some(tu) = tu {
+                self.statistics.reused += 1;
+                return Ok(tu);
+            }
+        }
+
+        self.statistics.parses += 1;
+        index
+            .parser(filepath)
+            .arguments(args)
+            .unsaved(unsaved)
+            .detailed_preprocessing_record(true)
+            .parse()
+            .map_err(|err| Error::Parse(filepath.into(), err.to_string()))
+    }
+
+    /// Analyzes the sourcefile with the compiler args of all build `configurations`.
+    ///
+    /// Only the findings of all configurations are reported.
+    /// The sourcefile is parsed on top of the precompiled `header` that queued its analysis,
+    /// which is rebuilt if the edit `versions` of its files changed.
+    pub fn analyze_configurations(
+        &mut self,
+        filepath: &Path,
+        configurations: &[Vec<String>],
+        settings: &Settings,
+        unsaved: &[Unsaved],
+        header: Option<(&Path, &HashMap<PathBuf, usize>)>,
+    ) -> Result<Analysis> {
+        let mut result: Option<Analysis> = None;
+        for args in configurations {
+            let tu = self.parse(filepath, args, unsaved, header)?;
+            let analysis = analyze_unit(&tu, filepath, args, settings)?;
+            match &mut result {
+                Some(result) => result.intersect(analysis),
+                None => result = Some(analysis),
+            }
+        }
+        Ok(result.unwrap_or_default())
+    }
+}
+
+impl Drop for TranslationUnits<'_> {
+    fn drop(&mut self) {
+        for precompiled in self.headers.values().flatten() {
+            let _ = fs::remove_file(&precompiled.path);
+        }
+    }
+}
+
+/// Returns whether the unit has errors
+fn has_errors(tu: &TranslationUnit) -> bool {
+    tu.get_diagnostics()
+        .iter()
+        .any(|diag| matches!(diag.get_severity(), Severity::Error | Severity::Fatal))
+}
+
 /// Returns all includes that exist but are not referenced from the sourcefile
+///
+/// The `unsaved` files overlay the contents on disk.
 pub fn unused_includes(
     filepath: &Path,
     args: &[String],
-    ignore_includes: &regex::Regex,
-) -> Result<Vec<Include>, ()> {
-    match Index::new(&CLANG, false, true)
+    settings: &Settings,
+    unsaved: &[Unsaved],
+) -> Result<Analysis> {
+    let index = Index::new(&CLANG, false, true);
+    let tu = index
         .parser(filepath)
         .arguments(args)
+        .unsaved(unsaved)
         .detailed_preprocessing_record(true)
         .parse()
-    {
-        Ok(tu) => {
-            for diag in tu.get_diagnostics() {
-                println!("clang: {}", diag);
+        .map_err(|err| Error::Parse(filepath.into(), err.to_string()))?;
+    analyze_unit(&tu, filepath, args, settings)
+}
+
+/// Include graphs of a parsed sourcefile, marked by the uses of its symbols
+/// Use in the sourcefile that marked a file as used
+struct Mark<'tu> {
+    /// The used declaration, like a function, a type or a macro definition
+    declaration: Entity<'tu>,
+    file: FileID,
+    /// Location of the use in the sourcefile
+    line: usize,
+    column: usize,
+}
+
+impl<'tu> Mark<'tu> {
+    fn new(entity: &Entity, declaration: Entity<'tu>, file: FileID) -> Mark<'tu> {
+        let start = entity
+            .get_range()
+            .map(|r| r.get_start().get_file_location());
+        Mark {
+            declaration,
+            file,
+            line: start.map_or(0, |s| s.line as usize),
+            column: start.map_or(1, |s| s.column as usize),
+        }
+    }
+}
+
+struct MarkedUnit<'tu> {
+    file: File<'tu>,
+    includes: IncludeGraph,
+    /// Includes required by uses of complete types
+    complete: IncludeGraph,
+    ignored: Vec<IgnoredInclude>,
+    names: HashMap<FileID, (String, PathBuf)>,
+    pragmas: HashMap<FileID, FilePragmas>,
+    uses: Vec<(Entity<'tu>, Entity<'tu>)>,
+    incomplete: Vec<(Entity<'tu>, String)>,
+    /// Namespaces referenced by the sourcefile, with the referencing entities
+    namespaces: Vec<(Entity<'tu>, Entity<'tu>)>,
+    /// Uses that marked the files of `includes`
+    marks: Vec<Mark<'tu>>,
+}
+
+impl<'tu> MarkedUnit<'tu> {
+    /// Builds the include graph of the parsed sourcefile and marks the used includes
+    fn new(tu: &'tu TranslationUnit, filepath: &Path, settings: &Settings) -> Result<Self> {
+        let file = tu
+            .get_file(filepath)
+            .ok_or_else(|| Error::Parse(filepath.into(), "Missing main file".into()))?;
+
+        let mut includes = IncludeGraph::new();
+        let mut ignored = Vec::new();
+        let mut names = HashMap::new();
+        let mut pragmas = HashMap::new();
+
+        tu.get_entity().visit_children(|entity, _| {
+            find_includes(
+                entity,
+                settings,
+                &mut includes,
+                &mut ignored,
+                &mut names,
+                &mut pragmas,
+            )
+        });
+
+        if !settings.mappings.is_empty() {
+            insert_mappings(&mut includes, &names, &settings.mappings);
+        }
+
+        let mut unit = MarkedUnit {
+            file,
+            complete: includes.clone(),
+            includes,
+            ignored,
+            names,
+            pragmas,
+            uses: Vec::new(),
+            incomplete: Vec::new(),
+            namespaces: Vec::new(),
+            marks: Vec::new(),
+        };
+        tu.get_entity()
+            .visit_children(|entity, parent| unit.mark_includes(entity, parent));
+        unit.mark_conditional_macros(tu.get_entity());
+        unit.mark_namespaces(tu.get_entity());
+
+        Ok(unit)
+    }
+
+    /// Marks all necessary includes.
+    ///
+    /// Also collects the direct references from the main file to other files.
+    /// The `complete` graph only contains the uses that require complete types,
+    /// the others are collected with their forward declarations in `incomplete`.
+    fn mark_includes(&mut self, entity: Entity<'tu>, parent: Entity<'tu>) -> EntityVisitResult {
+        if !entity.is_in_main_file() {
+            return EntityVisitResult::Continue;
+        }
+        // Qualifiers and `using namespace` are resolved after all other uses
+        if entity.get_kind() == EntityKind::NamespaceRef {
+            if let Some(namespace) = entity.get_reference() {
+                self.namespaces.push((entity, namespace));
+            }
+            return EntityVisitResult::Continue;
+        }
+
+        let mut forward = None;
+        if let Some(reference) = get_referenced(&entity) {
+            if reference != entity && !reference.is_in_main_file() {
+                self.uses.push((entity, reference));
+                if entity.get_kind() == EntityKind::TypeRef && !requires_complete_type(&parent) {
+                    forward = forward_declaration(&reference).map(|decl| (reference, decl));
+                }
+            }
+        } else if let Some(reference) = entity.get_reference() {
+            // Other references (like member accesses) require the complete type
+            if !reference.is_in_main_file() {
+                if let Some(to) = providing_file(&reference, &self.names) {
+                    self.complete.mark_used(&to);
+                }
             }
+        }
 
-            let mut includes = IncludeGraph::new();
+        // Types without references, like `auto` or implicit constructor calls
+        if let Some(declaration) = get_type_declaration(&entity) {
+            if !declaration.is_in_main_file() {
+                if let Some(to) = providing_file(&declaration, &self.names) {
+                    self.mark(&entity, declaration, to);
+                    self.complete.mark_used(&to);
+                }
+            }
+        }
 
-            tu.get_entity()
-                .visit_children(|entity, _| find_includes(entity, ignore_includes, &mut includes));
+        visit_references(entity, &mut |declaration| {
+            if let Some(to) = providing_file(&declaration, &self.names) {
+                self.includes.mark_used(&to);
+                self.marks.push(Mark::new(&entity, declaration, to));
+            }
+        });
+        if let Some(forward) = forward {
+            self.incomplete.push(forward);
+        } else {
+            mark_includes_impl(entity, &mut self.complete, &self.names);
+        }
 
-            tu.get_entity()
-                .visit_children(|entity, _| mark_includes(entity, &mut includes));
+        EntityVisitResult::Recurse
+    }
 
-            if let Some(file) = tu.get_file(&filepath) {
-                let unused = includes.unused(&file.get_id());
-                let mut result = Vec::with_capacity(unused.len());
+    /// Marks the `file` as used by the `declaration` the `entity` refers to
+    fn mark(&mut self, entity: &Entity, declaration: Entity<'tu>, file: FileID) {
+        self.includes.mark_used(&file);
+        self.marks.push(Mark::new(entity, declaration, file));
+    }
 
-                file.visit_includes(|entity, source_range| {
-                    collect_unused_includes(entity, source_range, &unused, &mut result);
-                    true
+    /// Marks the headers defining the macros tested in the preprocessor conditionals
+    /// (`#if`, `#ifdef`, ...) of the sourcefile.
+    fn mark_conditional_macros(&mut self, tu: Entity<'tu>) {
+        let macros = fileio::conditional_macros(&self.file.get_contents().unwrap_or_default());
+        if macros.is_empty() {
+            return;
+        }
+        for entity in tu.get_children() {
+            if entity.get_kind() != EntityKind::MacroDefinition || entity.is_in_main_file() {
+                continue;
+            }
+            let line = match entity.get_name().and_then(|name| macros.get(&name)) {
+                Some(line) => *line,
+                None => continue,
+            };
+            if let Some(to) = entity.get_sourcefile() {
+                self.includes.mark_used(&to.get_id());
+                self.complete.mark_used(&to.get_id());
+                self.marks.push(Mark {
+                    declaration: entity,
+                    file: to.get_id(),
+                    line,
+                    column: 1,
                 });
+            }
+        }
+    }
 
-                Ok(result)
-            } else {
-                Err(())
+    /// Marks a direct include declaring each referenced namespace, if no other one is used.
+    ///
+    /// Namespaces are opened by many headers, so a reference (e.g. `using namespace`)
+    /// is no use of the header clang resolves it to. It only keeps one direct include
+    /// that opens the namespace itself, but is neither required for complete types
+    /// nor a reason for missing includes.
+    fn mark_namespaces(&mut self, tu: Entity<'tu>) {
+        if self.namespaces.is_empty() {
+            return;
+        }
+        let mut files = HashMap::new();
+        namespace_files(tu, &mut files);
+        let direct = self.direct_includes();
+        let unused = self
+            .includes
+            .unused(&self.file.get_id())
+            .into_iter()
+            .copied()
+            .collect::<HashSet<_>>();
+
+        let mut resolved = HashSet::new();
+        for (entity, namespace) in mem::take(&mut self.namespaces) {
+            let usr = match namespace.get_usr() {
+                Some(usr) if resolved.insert(usr.0.clone()) => usr.0,
+                _ => continue,
+            };
+            let opening = direct
+                .iter()
+                .map(|(id, _)| *id)
+                .filter(|id| files.get(&usr).is_some_and(|files| files.contains(id)))
+                .collect::<Vec<_>>();
+            if opening.iter().all(|id| unused.contains(id)) {
+                if let Some(id) = opening.first() {
+                    self.mark(&entity, namespace, *id);
+                }
+            }
+        }
+    }
+
+    /// Direct includes of the sourcefile
+    fn direct_includes(&self) -> Vec<(FileID, Include)> {
+        let mut direct = Vec::new();
+        self.file.visit_includes(|entity, source_range| {
+            collect_direct_includes(entity, source_range, &mut direct);
+            true
+        });
+        direct
+    }
+}
+
+/// Returns all includes that exist but are not referenced from the parsed sourcefile
+fn analyze_unit(
+    tu: &TranslationUnit,
+    filepath: &Path,
+    args: &[String],
+    settings: &Settings,
+) -> Result<Analysis> {
+    let mut analysis = Analysis::default();
+
+    for diag in tu.get_diagnostics() {
+        info!("clang: {}", diag);
+        analysis.diagnostics.push(Diagnostic::new(&diag));
+    }
+
+    let unit = MarkedUnit::new(tu, filepath, settings)?;
+    let direct = unit.direct_includes();
+    let MarkedUnit {
+        file,
+        mut includes,
+        mut complete,
+        ignored,
+        names,
+        mut pragmas,
+        uses,
+        incomplete,
+        ..
+    } = unit;
+    analysis.ignored = ignored;
+
+    let direct_ids = direct.iter().map(|(id, _)| *id).collect::<HashSet<_>>();
+
+    // Only classes declared in direct includes can be forward declared
+    for (declaration, _) in &incomplete {
+        if let Some(to) = declaration.get_sourcefile() {
+            if !direct_ids.contains(&to.get_id()) {
+                complete.mark_used(&to.get_id());
             }
         }
-        Err(err) => {
-            eprintln!("Parsing error: {}", err);
-            Err(())
+    }
+
+    // Direct includes and the headers exported by them
+    let mut provided = direct_ids.clone();
+    for id in &direct_ids {
+        provided.extend(includes.exported(id));
+    }
+    let no_include = file_pragmas(&mut pragmas, &file).no_include.clone();
+    analysis.missing =
+        collect_missing_includes(&uses, &provided, &names, &pragmas, &no_include, settings);
+    respell_includes(&mut analysis.missing, filepath, args, settings);
+
+    let unused = includes.unused(&file.get_id());
+    let unused_complete = complete.unused(&file.get_id());
+
+    if settings.associated_header_first && !settings.is_header_file(filepath) {
+        analysis.associated_not_first = direct
+            .iter()
+            .skip(1)
+            .find(|(_, i)| settings.is_associated_header(filepath, &i.path))
+            .map(|(_, include)| include.clone());
+    }
+
+    analysis.unused = direct
+        .iter()
+        .filter(|(id, _)| unused.contains(id))
+        .map(|(_, include)| include.clone())
+        .collect();
+    analysis.forward =
+        collect_forward_declarations(&direct, &unused, &unused_complete, &incomplete);
+
+    Ok(analysis)
+}
+
+/// Symbol that requires an include
+#[derive(Debug, Clone, Serialize)]
+pub struct Reference {
+    pub symbol: String,
+    /// Location of the first use in the sourcefile
+    pub line: usize,
+    pub column: usize,
+    /// Header declaring the symbol
+    pub header: PathBuf,
+    /// Include chain from the sourcefile to the header
+    pub chain: Vec<PathBuf>,
+}
+
+/// Explanation why an include of the sourcefile is required
+#[derive(Debug, Serialize)]
+pub struct Explanation {
+    pub file: PathBuf,
+    pub include: Include,
+    /// The include is never reported as unused
+    pub ignored: Option<IgnoreReason>,
+    pub references: Vec<Reference>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.file.to_string_lossy();
+        write!(
+            f,
+            "{}:{}:{}: '{}'",
+            file, self.include.line, self.include.column, self.include.name
+        )?;
+        if let Some(reason) = self.ignored {
+            writeln!(f, " is ignored ({:?})", reason)?;
+        } else if self.references.is_empty() {
+            writeln!(f, " is not used")?;
+        } else {
+            writeln!(f, " is required by:")?;
+        }
+        for reference in &self.references {
+            let chain = reference
+                .chain
+                .iter()
+                .map(|p| p.to_string_lossy())
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "  {}:{}:{}: '{}' declared in {}",
+                file,
+                reference.line,
+                reference.column,
+                reference.symbol,
+                chain.join(" -> ")
+            )?;
         }
+        Ok(())
     }
 }
 
+/// Explains which symbols of the sourcefile require the given `include`.
+///
+/// The `include` is matched against the name or the end of the path of the direct includes.
+pub fn why(
+    filepath: &Path,
+    args: &[String],
+    settings: &Settings,
+    unsaved: &[Unsaved],
+    include: &str,
+) -> Result<Explanation> {
+    let index = Index::new(&CLANG, false, true);
+    let tu = index
+        .parser(filepath)
+        .arguments(args)
+        .unsaved(unsaved)
+        .detailed_preprocessing_record(true)
+        .parse()
+        .map_err(|err| Error::Parse(filepath.into(), err.to_string()))?;
+
+    let unit = MarkedUnit::new(&tu, filepath, settings)?;
+    let (id, include) = unit
+        .direct_includes()
+        .into_iter()
+        .find(|(_, i)| i.name == include || i.path.ends_with(include))
+        .ok_or_else(|| Error::NotIncluded(filepath.into(), include.into()))?;
+    let ignored = unit
+        .ignored
+        .iter()
+        .find(|i| i.include.line == include.line)
+        .map(|i| i.reason);
+
+    // The shortest paths of the marked graph, exactly like for the unused includes
+    let MarkedUnit {
+        file,
+        mut includes,
+        names,
+        marks,
+        ..
+    } = unit;
+    includes.unused(&file.get_id());
+
+    let mut references: Vec<Reference> = Vec::new();
+    for mark in marks {
+        let chain = includes.chain(&mark.file);
+        if chain.first() != Some(&id) {
+            continue;
+        }
+        let symbol = mark
+            .declaration
+            .get_name()
+            .or_else(|| mark.declaration.get_display_name())
+            .unwrap_or_default();
+        let header = match names.get(&mark.file) {
+            Some((_, path)) => path.clone(),
+            None => continue,
+        };
+        if references
+            .iter()
+            .any(|r| r.symbol == symbol && r.header == header)
+        {
+            continue;
+        }
+        references.push(Reference {
+            symbol,
+            line: mark.line,
+            column: mark.column,
+            header,
+            chain: chain
+                .iter()
+                .filter_map(|id| names.get(id).map(|(_, path)| path.clone()))
+                .collect(),
+        });
+    }
+
+    Ok(Explanation {
+        file: filepath.into(),
+        include,
+        ignored,
+        references,
+    })
+}
+
+/// Error of a header that is parsed on its own
+#[derive(Debug, Clone, Serialize)]
+pub struct HeaderError {
+    pub diagnostic: Diagnostic,
+    /// The missing include declaring the symbol of the error
+    pub fix: Option<Include>,
+}
+
+/// Result of the self-contained check of a header
+#[derive(Debug, Clone, Serialize)]
+pub struct HeaderCheck {
+    pub file: PathBuf,
+    pub errors: Vec<HeaderError>,
+}
+
+impl HeaderCheck {
+    /// Writes the errors as compiler-style diagnostics, followed by notes for the fixes
+    pub fn compiler<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        for error in &self.errors {
+            writeln!(out, "{}", error.diagnostic)?;
+            if let Some(fix) = &error.fix {
+                writeln!(
+                    out,
+                    "{}:{}:{}: note: missing include '{}'",
+                    self.file.to_string_lossy(),
+                    fix.line,
+                    fix.column,
+                    fix.name
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HeaderCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.errors.is_empty() {
+            return writeln!(f, "{} is self-contained", self.file.to_string_lossy());
+        }
+        writeln!(f, "{} is not self-contained:", self.file.to_string_lossy())?;
+        for error in &self.errors {
+            write!(f, "  {}", error.diagnostic)?;
+            if let Some(fix) = &error.fix {
+                write!(f, " (missing include '{}')", fix.name)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the header language for `-x` (like `c++-header`).
+///
+/// It is derived from an explicit `-x` or `-std` in the compiler args, the header extension
+/// or the extension of the sourcefile including it, defaulting to C++.
+fn header_language(filepath: &Path, args: &[String], source: Option<&Path>) -> &'static str {
+    let language = |name: &str| match name {
+        "c" | "c-header" => Some("c-header"),
+        "c++" | "c++-header" => Some("c++-header"),
+        "objective-c" | "objective-c-header" => Some("objective-c-header"),
+        "objective-c++" | "objective-c++-header" => Some("objective-c++-header"),
+        _ => None,
+    };
+
+    let explicit = args
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, arg)| match arg.as_str() {
+            "-x" => args.get(i + 1).and_then(|name| language(name)),
+            _ => arg.strip_prefix("-x").and_then(language),
+        });
+    let standard = || {
+        let std = args
+            .iter()
+            .rev()
+            .find_map(|arg| arg.strip_prefix("-std="))?;
+        language(if std.contains("++") { "c++" } else { "c" })
+    };
+    let extension = |path: &Path| match path.extension()?.to_str()? {
+        "hpp" | "hh" | "hxx" | "h++" | "cpp" | "cc" | "cxx" | "c++" | "C" => language("c++"),
+        "c" => language("c"),
+        "m" => language("objective-c"),
+        "mm" => language("objective-c++"),
+        _ => None,
+    };
+
+    explicit
+        .or_else(standard)
+        .or_else(|| extension(filepath))
+        .or_else(|| source.and_then(extension))
+        .unwrap_or("c++-header")
+}
+
+/// Checks whether the header compiles on its own (like `-x c++-header`, see `header_language`).
+///
+/// The fixing includes are found by parsing the `context` sourcefile (with its compiler args),
+/// that includes the header: Used headers that are not included by the header itself are missing.
+pub fn check_header(
+    filepath: &Path,
+    args: &[String],
+    context: Option<(&Path, &[String])>,
+    settings: &Settings,
+    unsaved: &[Unsaved],
+) -> Result<HeaderCheck> {
+    let index = Index::new(&CLANG, false, true);
+    let mut header_args = args.to_vec();
+    let language = header_language(filepath, args, context.map(|(source, _)| source));
+    header_args.extend(["-x".to_string(), language.to_string()]);
+    let tu = index
+        .parser(filepath)
+        .arguments(&header_args)
+        .unsaved(unsaved)
+        .parse()
+        .map_err(|err| Error::Parse(filepath.into(), err.to_string()))?;
+
+    let mut errors = Vec::new();
+    for diag in tu.get_diagnostics() {
+        if matches!(diag.get_severity(), Severity::Error | Severity::Fatal) {
+            info!("clang: {}", diag);
+            errors.push(HeaderError {
+                diagnostic: Diagnostic::new(&diag),
+                fix: None,
+            });
+        }
+    }
+
+    if let (false, Some((source, args))) = (errors.is_empty(), context) {
+        let missing = header_missing_includes(filepath, source, args, settings, unsaved)?;
+        for error in &mut errors {
+            error.fix = missing
+                .iter()
+                .find(|m| {
+                    m.symbols
+                        .iter()
+                        .any(|s| error.diagnostic.message.contains(&format!("'{}'", s)))
+                })
+                .map(|m| m.include.clone());
+        }
+    }
+
+    Ok(HeaderCheck {
+        file: filepath.into(),
+        errors,
+    })
+}
+
+/// Collects the headers used by the header, that are not included by the header itself,
+/// but by the `source` before it.
+fn header_missing_includes(
+    filepath: &Path,
+    source: &Path,
+    args: &[String],
+    settings: &Settings,
+    unsaved: &[Unsaved],
+) -> Result<Vec<MissingInclude>> {
+    let index = Index::new(&CLANG, false, true);
+    let tu = index
+        .parser(source)
+        .arguments(args)
+        .unsaved(unsaved)
+        .detailed_preprocessing_record(true)
+        .parse()
+        .map_err(|err| Error::Parse(source.into(), err.to_string()))?;
+
+    let header = tu
+        .get_file(filepath)
+        .ok_or_else(|| Error::NotIncluded(source.into(), filepath.to_string_lossy().into()))?
+        .get_id();
+
+    // All includes, including the ignored ones
+    let mut includes = IncludeGraph::new();
+    let mut names = HashMap::new();
+    let mut pragmas = HashMap::new();
+    tu.get_entity().visit_children(|entity, _| {
+        if entity.get_kind() == EntityKind::InclusionDirective {
+            if let (Some(from), Some(to)) = (entity.get_sourcefile(), entity.get_file()) {
+                if let Some(name) = entity.get_name() {
+                    names
+                        .entry(to.get_id())
+                        .or_insert_with(|| (name, to.get_path()));
+                }
+                file_pragmas(&mut pragmas, &to);
+                includes.insert(from.get_id(), to.get_id());
+            }
+        }
+        EntityVisitResult::Continue
+    });
+
+    let mut uses = Vec::new();
+    tu.get_entity().visit_children(|entity, _| {
+        if entity.get_sourcefile().map(|f| f.get_id()) != Some(header) {
+            return EntityVisitResult::Continue;
+        }
+        if let Some(reference) = get_referenced(&entity) {
+            if reference != entity {
+                uses.push((entity, reference));
+            }
+        }
+        if let Some(declaration) = get_type_declaration(&entity) {
+            uses.push((entity, declaration));
+        }
+        EntityVisitResult::Recurse
+    });
+
+    let provided = includes.reachable(&header);
+    let no_include = pragmas
+        .get(&header)
+        .map(|p| p.no_include.clone())
+        .unwrap_or_default();
+    let mut missing =
+        collect_missing_includes(&uses, &provided, &names, &pragmas, &no_include, settings);
+    respell_includes(&mut missing, filepath, args, settings);
+    Ok(missing)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -267,16 +1720,17 @@ mod test {
     fn test_include_relpath() {
         let dir = current_dir().unwrap().join("tests");
         let include_paths = [dir.join("src"), dir.join("include")];
+        let source_roots = Settings::default().source_roots;
 
         let include = Include::new("Base.hpp".into(), dir.join("src/Base.hpp"), 0);
         assert_eq!(
-            include.get_local(&dir.join("src/Main.cpp"), &include_paths),
+            include.get_local(&dir.join("src/Main.cpp"), &include_paths, &source_roots),
             Some("Base.hpp".into())
         );
 
         let include = Include::new("Classes.hpp".into(), dir.join("src/refs/Classes.hpp"), 0);
         assert_eq!(
-            include.get_local(&dir.join("src/Main.cpp"), &include_paths),
+            include.get_local(&dir.join("src/Main.cpp"), &include_paths, &source_roots),
             Some("refs/Classes.hpp".into())
         );
 
@@ -286,29 +1740,47 @@ mod test {
             0,
         );
         assert_eq!(
-            include.get_local(&dir.join("src/ref/Main.cpp"), &include_paths),
+            include.get_local(&dir.join("src/ref/Main.cpp"), &include_paths, &source_roots),
             Some("ExternalRef.hpp".into())
         );
 
         let include = Include::new("vector".into(), dir.join("/usr/lib/include/vector"), 0);
         assert_eq!(
-            include.get_local(&dir.join("Main.cpp"), &include_paths),
+            include.get_local(&dir.join("Main.cpp"), &include_paths, &source_roots),
             None
         );
+        assert_eq!(
+            include.statement(&dir.join("Main.cpp"), &include_paths, &source_roots),
+            IncludeStatement::Global("vector".into())
+        );
+
+        // Local include relative to the file, not as written by the first includer,
+        // also without include paths
+        let include = Include::new("../Base.hpp".into(), dir.join("src/Base.hpp"), 0);
+        assert_eq!(
+            include.statement(&dir.join("src/Main.cpp"), &[], &source_roots),
+            IncludeStatement::Local("Base.hpp".into())
+        );
+        let include = Include::new("Classes.hpp".into(), dir.join("src/refs/Classes.hpp"), 0);
+        assert_eq!(
+            include.statement(&dir.join("src/Main.cpp"), &include_paths, &source_roots),
+            IncludeStatement::Local("refs/Classes.hpp".into())
+        );
     }
 
     #[test]
     fn test_unused_includes() {
         let dir = current_dir().unwrap().join("tests/src/refs");
-        let ignore_includes = regex::Regex::new("(/private/|[_/]impl[_\\./])").unwrap();
+        let settings = Settings::default();
         let args: [String; 0] = [];
 
         for file in fs::read_dir(dir).unwrap() {
             let file = file.unwrap();
             if let Some(ext) = file.path().extension() {
                 if ext == "cpp" {
-                    let unused =
-                        unused_includes(&file.path(), &args, &ignore_includes).expect("Include Err");
+                    let unused = unused_includes(&file.path(), &args, &settings, &[])
+                        .expect("Include Err")
+                        .unused;
                     assert!(unused.is_empty(), "{:?}", &unused);
                 }
             }
@@ -318,9 +1790,286 @@ mod test {
     #[test]
     fn test_unused_includes_single() {
         let file = current_dir().unwrap().join("tests/src/refs/UsingT.cpp");
-        let ignore_includes = regex::Regex::new("(/private/|[_/]impl[_\\./])").unwrap();
+        let settings = Settings::default();
         let args: [String; 0] = [];
-        let unused = unused_includes(&file, &args, &ignore_includes).expect("Include Err");
+        let unused = unused_includes(&file, &args, &settings, &[])
+            .expect("Include Err")
+            .unused;
         assert!(unused.is_empty(), "{:?}", &unused);
     }
+
+    #[test]
+    fn test_missing_includes() {
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/missing/TransitiveT.cpp");
+        let args: [String; 0] = [];
+        let analysis =
+            unused_includes(&file, &args, &Settings::default(), &[]).expect("Include Err");
+        assert!(analysis.unused.is_empty(), "{:?}", &analysis.unused);
+
+        assert_eq!(analysis.missing.len(), 1, "{:?}", &analysis.missing);
+        let missing = &analysis.missing[0];
+        assert_eq!(missing.include.name, "Inner.hpp");
+        assert_eq!(missing.include.line, 5);
+        assert_eq!(missing.symbols, ["Inner"]);
+
+        // Named relative to the sourcefile instead of the including header,
+        // a local include even without include paths
+        let file = file.with_file_name("NestedT.cpp");
+        let analysis =
+            unused_includes(&file, &args, &Settings::default(), &[]).expect("Include Err");
+        assert_eq!(analysis.missing.len(), 1, "{:?}", &analysis.missing);
+        assert_eq!(analysis.missing[0].include.name, "Inner.hpp");
+        assert_eq!(
+            analysis.missing[0].statement,
+            IncludeStatement::Local("Inner.hpp".into())
+        );
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/namespaces/QualifiedT.cpp");
+        let args: [String; 0] = [];
+        let analysis =
+            unused_includes(&file, &args, &Settings::default(), &[]).expect("Include Err");
+
+        // The qualifier is no use of the first header opening the namespace
+        let unused = analysis.unused.iter().map(|i| &i.name).collect::<Vec<_>>();
+        assert_eq!(unused, ["Opens.hpp"]);
+        assert!(analysis.missing.is_empty(), "{:?}", &analysis.missing);
+    }
+
+    #[test]
+    fn test_forward_declarations() {
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/forward/ForwardT.cpp");
+        let args: [String; 0] = [];
+        let analysis =
+            unused_includes(&file, &args, &Settings::default(), &[]).expect("Include Err");
+        assert!(analysis.unused.is_empty(), "{:?}", &analysis.unused);
+
+        // Bar is accessed and requires the complete type
+        assert_eq!(analysis.forward.len(), 1, "{:?}", &analysis.forward);
+        let forward = &analysis.forward[0];
+        assert_eq!(forward.include.name, "Foo.hpp");
+        assert_eq!(
+            forward.declarations,
+            ["namespace outer { namespace inner { class Foo; } }"]
+        );
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/pragmas/PragmasT.cpp");
+        let args: [String; 0] = [];
+        let analysis =
+            unused_includes(&file, &args, &Settings::default(), &[]).expect("Include Err");
+        assert!(analysis.unused.is_empty(), "{:?}", &analysis.unused);
+
+        assert_eq!(analysis.ignored.len(), 1, "{:?}", &analysis.ignored);
+        assert_eq!(analysis.ignored[0].include.name, "Kept.hpp");
+        assert_eq!(analysis.ignored[0].reason, IgnoreReason::KeepComment);
+
+        // Exported is provided by Exporter.hpp, Detail.hpp is private
+        assert_eq!(analysis.missing.len(), 1, "{:?}", &analysis.missing);
+        assert_eq!(analysis.missing[0].include.name, "Public.hpp");
+        assert_eq!(analysis.missing[0].symbols, ["Detail"]);
+    }
+
+    #[test]
+    fn test_why() {
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/missing/TransitiveT.cpp");
+        let args: [String; 0] = [];
+        let explanation =
+            why(&file, &args, &Settings::default(), &[], "Outer.hpp").expect("Include Err");
+        assert_eq!(explanation.include.line, 1);
+        assert!(explanation.ignored.is_none());
+
+        let symbols = explanation
+            .references
+            .iter()
+            .map(|r| r.symbol.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(symbols, ["Outer", "Inner"]);
+        let inner = &explanation.references[1];
+        assert_eq!(inner.line, 5);
+        assert_eq!(inner.chain.len(), 2);
+        assert!(inner.chain[1].ends_with("Inner.hpp"));
+
+        // Shared.hpp is reached through both includes, the explanation matches the analysis
+        let file = current_dir().unwrap().join("tests/src/why/TieT.cpp");
+        let settings = Settings::default();
+        let unused = unused_includes(&file, &args, &settings, &[])
+            .expect("Include Err")
+            .unused;
+        assert_eq!(unused.len(), 1, "{:?}", unused);
+        assert_eq!(unused[0].name, "Unused.hpp");
+        let explanation = why(&file, &args, &settings, &[], "Unused.hpp").expect("Include Err");
+        assert!(explanation.references.is_empty());
+        let explanation = why(&file, &args, &settings, &[], "Used.hpp").expect("Include Err");
+        assert_eq!(explanation.references.len(), 2);
+
+        // Macros tested in conditions, like in the analysis
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/refs/ConditionalT.cpp");
+        let explanation =
+            why(&file, &args, &settings, &[], "Conditional.hpp").expect("Include Err");
+        let mut symbols = explanation
+            .references
+            .iter()
+            .map(|r| (r.symbol.as_str(), r.line))
+            .collect::<Vec<_>>();
+        symbols.sort();
+        assert_eq!(symbols, [("FEATURE_X", 4), ("VERSION", 4)]);
+    }
+
+    #[test]
+    fn test_header_language() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let header = Path::new("src/Header.h");
+        assert_eq!(header_language(header, &[], None), "c++-header");
+        assert_eq!(
+            header_language(header, &args(&["-x", "c"]), None),
+            "c-header"
+        );
+        assert_eq!(
+            header_language(header, &args(&["-xc++"]), None),
+            "c++-header"
+        );
+        assert_eq!(
+            header_language(header, &args(&["-std=gnu11"]), None),
+            "c-header"
+        );
+        assert_eq!(
+            header_language(header, &args(&["-std=c++17"]), None),
+            "c++-header"
+        );
+        assert_eq!(
+            header_language(header, &[], Some(Path::new("src/main.c"))),
+            "c-header"
+        );
+        assert_eq!(
+            header_language(Path::new("Header.hpp"), &[], Some(Path::new("main.c"))),
+            "c++-header"
+        );
+    }
+
+    #[test]
+    fn test_check_header() {
+        let dir = current_dir().unwrap().join("tests/src/selfcontained");
+        let args: [String; 0] = [];
+        let settings = Settings::default();
+
+        let check = check_header(&dir.join("Base.hpp"), &args, None, &settings, &[]).unwrap();
+        assert!(check.errors.is_empty(), "{:?}", &check.errors);
+
+        let source = dir.join("ContextT.cpp");
+        let check = check_header(
+            &dir.join("Derived.hpp"),
+            &args,
+            Some((&source, &args)),
+            &settings,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(check.errors.len(), 1, "{:?}", &check.errors);
+        let fix = check.errors[0].fix.as_ref().expect("Missing fix");
+        assert_eq!(fix.name, "Base.hpp");
+    }
+
+    #[test]
+    fn test_translation_units() {
+        let dir = current_dir().unwrap().join("tests/src/pch");
+        let header = dir.join("Shared.hpp");
+        let settings = Settings::default();
+        let configurations = [vec![]];
+        let index = create_index();
+        let mut units = TranslationUnits::new(&index, 1);
+        let mut versions = HashMap::new();
+
+        let analyze = |units: &mut TranslationUnits, name: &str, versions: &HashMap<_, _>| {
+            let analysis = units
+                .analyze_configurations(
+                    &dir.join(name),
+                    &configurations,
+                    &settings,
+                    &[],
+                    Some((header.as_path(), versions)),
+                )
+                .unwrap();
+            let unused = analysis.unused.iter().map(|i| &i.name).collect::<Vec<_>>();
+            assert_eq!(unused, ["Unused.hpp"], "{}", name);
+        };
+
+        // The header is precompiled once for all files including it
+        analyze(&mut units, "FirstT.cpp", &versions);
+        analyze(&mut units, "SecondT.cpp", &versions);
+        assert_eq!(
+            units.statistics(),
+            ParseStatistics {
+                parses: 0,
+                precompiled: 1,
+                reused: 2,
+            }
+        );
+
+        // Macros tested by the header are defined before it
+        analyze(&mut units, "ExtraT.cpp", &versions);
+        assert_eq!(units.statistics().parses, 1);
+        assert_eq!(units.statistics().precompiled, 1);
+
+        // Rebuilt after the include of the header was modified
+        versions.insert(dir.join("Inner.hpp"), 1);
+        analyze(&mut units, "FirstT.cpp", &versions);
+        assert_eq!(
+            units.statistics(),
+            ParseStatistics {
+                parses: 1,
+                precompiled: 2,
+                reused: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_intersect() {
+        let mut analysis = Analysis {
+            unused: vec![
+                Include::new("A.hpp".into(), "/src/A.hpp".into(), 1),
+                Include::new("B.hpp".into(), "/src/B.hpp".into(), 2),
+            ],
+            ..Analysis::default()
+        };
+        analysis.intersect(Analysis {
+            unused: vec![Include::new("B.hpp".into(), "/src/B.hpp".into(), 2)],
+            ..Analysis::default()
+        });
+        assert_eq!(analysis.unused.len(), 1);
+        assert_eq!(analysis.unused[0].name, "B.hpp");
+    }
+
+    #[test]
+    fn test_configurations() {
+        let file = current_dir()
+            .unwrap()
+            .join("tests/src/variants/VariantT.cpp");
+        let settings = Settings::default();
+
+        let analysis = analyze_configurations(&file, &[vec![]], &settings, &[]).unwrap();
+        assert_eq!(analysis.unused.len(), 1, "{:?}", &analysis.unused);
+
+        // Used with ENABLE_FOO
+        let configurations = [vec![], vec!["-DENABLE_FOO".to_string()]];
+        let analysis = analyze_configurations(&file, &configurations, &settings, &[]).unwrap();
+        assert!(analysis.unused.is_empty(), "{:?}", &analysis.unused);
+    }
 }
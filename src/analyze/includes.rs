@@ -1,38 +1,46 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::iter;
-use std::usize;
 
 pub type FileID = (u64, u64, u64);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct IncludeEntry {
     includes: HashSet<FileID>,
+    /// Includes whose symbols are provided by this file
+    exports: HashSet<FileID>,
     used: bool,
     costs: usize,
+    /// Direct include of the start through which this file is reached
     pred: Option<FileID>,
+    /// File including this file on the shortest path
+    parent: Option<FileID>,
 }
 
 impl IncludeEntry {
     fn new() -> IncludeEntry {
         IncludeEntry {
             includes: HashSet::new(),
+            exports: HashSet::new(),
             used: false,
             costs: 0,
             pred: None,
+            parent: None,
         }
     }
 
     fn new_with(include: FileID) -> IncludeEntry {
         IncludeEntry {
             includes: iter::once(include).collect(),
+            exports: HashSet::new(),
             used: false,
             costs: 0,
             pred: None,
+            parent: None,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IncludeGraph {
     includes: HashMap<FileID, IncludeEntry>,
 }
@@ -56,6 +64,48 @@ impl IncludeGraph {
         }
     }
 
+    /// Inserts an include whose symbols are provided by the including file.
+    ///
+    /// The exported file is reached through its exporter without additional costs.
+    pub fn insert_export(&mut self, from: FileID, to: FileID) {
+        self.insert(from, to);
+        if let Some(entry) = self.includes.get_mut(&from) {
+            entry.exports.insert(to);
+        }
+    }
+
+    /// Returns the files that are exported by the given file (transitively)
+    pub fn exported(&self, from: &FileID) -> HashSet<FileID> {
+        let mut result = HashSet::new();
+        let mut stack = vec![*from];
+        while let Some(file) = stack.pop() {
+            if let Some(entry) = self.includes.get(&file) {
+                for export in &entry.exports {
+                    if result.insert(*export) {
+                        stack.push(*export);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the given file and all files it includes (transitively)
+    pub fn reachable(&self, from: &FileID) -> HashSet<FileID> {
+        let mut result: HashSet<FileID> = iter::once(*from).collect();
+        let mut stack = vec![*from];
+        while let Some(file) = stack.pop() {
+            if let Some(entry) = self.includes.get(&file) {
+                for include in &entry.includes {
+                    if result.insert(*include) {
+                        stack.push(*include);
+                    }
+                }
+            }
+        }
+        result
+    }
+
     pub fn mark_used(&mut self, key: &FileID) {
         if let Some(entry) = self.includes.get_mut(key) {
             entry.used = true;
@@ -86,64 +136,134 @@ impl IncludeGraph {
         result
     }
 
-    /// Inspired by the Bellman-Ford-Moore algorithm
-    fn shortest_paths(&mut self, start: &FileID) {
-        for node in self.includes.values_mut() {
-            node.costs = usize::MAX - 1;
+    /// Returns the include chain from the direct include of the start to the given file.
+    ///
+    /// Only valid after the shortest paths have been computed by [`IncludeGraph::unused`].
+    pub fn chain(&self, file: &FileID) -> Vec<FileID> {
+        let mut chain = Vec::new();
+        let mut current = *file;
+        while let Some(entry) = self.includes.get(&current) {
+            if entry.pred.is_none() || chain.len() > self.len() {
+                // Unreachable
+                return Vec::new();
+            }
+            chain.push(current);
+            if entry.pred == Some(current) {
+                break;
+            }
+            match entry.parent {
+                Some(parent) => current = parent,
+                None => return Vec::new(),
+            }
         }
+        chain.reverse();
+        chain
+    }
 
-        if let Some(s_node) = self.includes.get_mut(start) {
-            s_node.costs = 0;
-            s_node.pred = Some(*start);
+    /// Breadth-first search from the start, visiting the files level by level of their costs.
+    ///
+    /// Exported includes have no costs and belong to the level of their exporter.
+    /// On equal costs, files reached through used files are preferred,
+    /// then the files reached first, with the includes of each file in the order of their ids.
+    /// The candidates of a level are kept in two buckets, for used and unused parents,
+    /// so a file is only finalized through an unused file if no used one is left.
+    /// Apart from sorting the includes of each file, this is linear in the size of the graph.
+    fn shortest_paths(&mut self, start: &FileID) {
+        for node in self.includes.values_mut() {
+            node.costs = usize::MAX;
+            node.pred = None;
+            node.parent = None;
         }
 
-        let edges = self.edges();
-
-        // Setup direct successors (set predecessors to themselves)
-        for direct_successor in edges.iter().filter(|e| &e.0 == start) {
-            if let Some(node) = self.includes.get_mut(&direct_successor.1) {
-                node.costs = 1;
-                node.pred = Some(direct_successor.1);
+        match self.includes.get_mut(start) {
+            Some(s_node) => {
+                s_node.costs = 0;
+                s_node.pred = Some(*start);
             }
+            None => return,
         }
 
-        for _ in 1..self.len() {
-            for (u, v) in &edges {
-                if let Some(u_node) = self.includes.get(u) {
-                    let (u_costs, u_pred) = (u_node.costs, u_node.pred);
+        let sorted = |files: &HashSet<FileID>| {
+            let mut files = files.iter().copied().collect::<Vec<_>>();
+            files.sort_unstable();
+            files
+        };
 
-                    if let Some(v_node) = self.includes.get_mut(v) {
-                        if u_costs + 1 < v_node.costs {
-                            v_node.costs = u_costs + 1;
-                            v_node.pred = u_pred;
-                        }
+        let mut visited: HashSet<FileID> = iter::once(*start).collect();
+        let mut level = vec![*start];
+        let mut costs = 0;
+        while !level.is_empty() {
+            costs += 1;
+            // Candidate edges of the next level, through used and unused parents
+            let mut used = VecDeque::new();
+            let mut unused = VecDeque::new();
+            for u in &level {
+                let u_node = &self.includes[u];
+                let bucket = if u_node.used { &mut used } else { &mut unused };
+                for v in sorted(&u_node.includes) {
+                    // Direct includes of the start always have costs
+                    if *u == *start || !u_node.exports.contains(&v) {
+                        bucket.push_back((*u, v));
                     }
                 }
             }
-        }
-    }
 
-    /// Returns all edges, stating with edges to used nodes.
-    fn edges(&self) -> Vec<(FileID, FileID)> {
-        let mut unused_edges = Vec::with_capacity(self.len());
-        let mut used_edges = Vec::new();
-        for (key, value) in &self.includes {
-            for to in &value.includes {
-                if value.used {
-                    used_edges.push((*key, *to));
+            level.clear();
+            while let Some((u, v)) = used.pop_front().or_else(|| unused.pop_front()) {
+                if visited.contains(&v) || !self.includes.contains_key(&v) {
+                    continue; // Unused file without includes
+                }
+                visited.insert(v);
+                level.push(v);
+
+                // Direct includes of the start are their own predecessors
+                let pred = if u == *start {
+                    Some(v)
                 } else {
-                    unused_edges.push((*key, *to));
+                    self.includes[&u].pred
+                };
+                let v_node = self.includes.get_mut(&v).unwrap();
+                v_node.costs = costs;
+                v_node.pred = pred;
+                v_node.parent = Some(u);
+
+                // Exported includes are reached without additional costs
+                let bucket = if v_node.used { &mut used } else { &mut unused };
+                for w in sorted(&v_node.exports) {
+                    if !visited.contains(&w) {
+                        bucket.push_back((v, w));
+                    }
                 }
             }
         }
-        used_edges.extend(unused_edges);
-        used_edges
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::time::Instant;
+
+    /// Synthetic include graph of the main file `(0, 0, 0)` with `layers` of `width` headers.
+    ///
+    /// Each header includes three headers of the next layer, the last layer is used.
+    fn synthetic_graph(layers: u64, width: u64) -> IncludeGraph {
+        let mut graph = IncludeGraph::new();
+        for i in 0..width {
+            graph.insert((0, 0, 0), (1, i, 0));
+        }
+        for layer in 1..layers {
+            for i in 0..width {
+                for k in 0..3 {
+                    graph.insert((layer, i, 0), (layer + 1, (i * 7 + k * 13) % width, 0));
+                }
+            }
+        }
+        for i in 0..width {
+            graph.mark_used(&(layers, i, 0));
+        }
+        graph
+    }
 
     #[test]
     fn test_use_shortest_paths() {
@@ -175,5 +295,139 @@ mod test {
             &iter::once(&unused).collect::<HashSet<_>>(),
             &graph.unused(&(0, 0, 0))
         );
+
+        assert_eq!(graph.chain(&(3, 0, 0)), [(0, 0, 1), (3, 0, 0)]);
+        assert_eq!(graph.chain(&(0, 1, 0)), [(0, 1, 0)]);
+    }
+
+    #[test]
+    fn test_exports() {
+        let mut graph = IncludeGraph::new();
+        graph.insert((0, 0, 0), (1, 0, 0));
+        graph.insert((1, 0, 0), (3, 0, 0)); // used, costs 2
+
+        graph.insert((0, 0, 0), (2, 0, 0));
+        graph.insert_export((2, 0, 0), (3, 0, 0)); // provided by (2, 0, 0)
+
+        graph.mark_used(&(3, 0, 0));
+
+        assert_eq!(
+            graph.exported(&(2, 0, 0)),
+            iter::once((3, 0, 0)).collect::<HashSet<_>>()
+        );
+        assert_eq!(
+            graph.reachable(&(1, 0, 0)),
+            [(1, 0, 0), (3, 0, 0)].into_iter().collect::<HashSet<_>>()
+        );
+        let unused = (1, 0, 0);
+        assert_eq!(
+            &iter::once(&unused).collect::<HashSet<_>>(),
+            &graph.unused(&(0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_prefer_used() {
+        let mut graph = IncludeGraph::new();
+        graph.insert((0, 0, 0), (1, 0, 0));
+        graph.insert((0, 0, 0), (2, 0, 0)); // used
+        graph.insert((1, 0, 0), (3, 0, 0));
+        graph.insert((2, 0, 0), (3, 0, 0)); // used, same costs
+
+        graph.mark_used(&(2, 0, 0));
+        graph.mark_used(&(3, 0, 0));
+
+        let unused = (1, 0, 0);
+        assert_eq!(
+            &iter::once(&unused).collect::<HashSet<_>>(),
+            &graph.unused(&(0, 0, 0))
+        );
+        assert_eq!(graph.chain(&(3, 0, 0)), [(2, 0, 0), (3, 0, 0)]);
+    }
+
+    #[test]
+    fn test_prefer_used_exports() {
+        let mut graph = IncludeGraph::new();
+        graph.insert((0, 0, 0), (1, 0, 0));
+        graph.insert((0, 0, 0), (2, 0, 0)); // used
+        graph.insert_export((1, 0, 0), (3, 0, 0));
+        graph.insert_export((2, 0, 0), (3, 0, 0)); // used, same costs
+
+        graph.mark_used(&(2, 0, 0));
+        graph.mark_used(&(3, 0, 0));
+
+        let unused = (1, 0, 0);
+        assert_eq!(
+            &iter::once(&unused).collect::<HashSet<_>>(),
+            &graph.unused(&(0, 0, 0))
+        );
+        assert_eq!(graph.chain(&(3, 0, 0)), [(2, 0, 0), (3, 0, 0)]);
+    }
+
+    #[test]
+    fn test_large_graph() {
+        let (layers, width) = (40, 250);
+        let mut graph = synthetic_graph(layers, width);
+
+        let unused = graph.unused(&(0, 0, 0)).len();
+        assert!(unused < width as usize);
+        assert_eq!(graph.chain(&(layers, 0, 0)).len(), layers as usize);
+    }
+
+    /// Costs of the shortest paths, computed by the former Bellman-Ford relaxation (O(V*E))
+    fn bellman_ford(graph: &IncludeGraph, start: &FileID) -> HashMap<FileID, usize> {
+        let mut costs: HashMap<FileID, usize> = graph
+            .includes
+            .keys()
+            .map(|file| (*file, usize::MAX - 1))
+            .collect();
+        costs.insert(*start, 0);
+        for _ in 1..graph.len() {
+            for (u, u_node) in &graph.includes {
+                for v in &u_node.includes {
+                    let edge = if u != start && u_node.exports.contains(v) {
+                        0
+                    } else {
+                        1
+                    };
+                    let u_costs = costs[u];
+                    if let Some(v_costs) = costs.get_mut(v) {
+                        *v_costs = (*v_costs).min(u_costs + edge);
+                    }
+                }
+            }
+        }
+        costs
+    }
+
+    /// Compares the search with the Bellman-Ford relaxation on a synthetic graph.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench_shortest_paths`.
+    #[test]
+    #[ignore]
+    fn bench_shortest_paths() {
+        let mut graph = synthetic_graph(20, 100);
+        for i in 0..100 {
+            // Exported includes of the layers
+            graph.insert_export((5, i, 0), (6, (i + 1) % 100, 0));
+        }
+
+        let start = Instant::now();
+        graph.shortest_paths(&(0, 0, 0));
+        let search = start.elapsed();
+
+        let start = Instant::now();
+        let costs = bellman_ford(&graph, &(0, 0, 0));
+        let reference = start.elapsed();
+
+        for (file, entry) in &graph.includes {
+            assert_eq!(entry.costs, costs[file], "{:?}", file);
+        }
+        println!(
+            "{} files: search {:?}, Bellman-Ford {:?}",
+            graph.len(),
+            search,
+            reference
+        );
     }
 }
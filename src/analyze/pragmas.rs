@@ -0,0 +1,114 @@
+use regex::Regex;
+
+lazy_static::lazy_static! {
+    static ref RE_PRAGMA: Regex = Regex::new(
+        "//[ \\t]*IWYU[ \\t]+pragma:[ \\t]*(\\w+)(?:[ \\t]*,?[ \\t]*(?:include[ \\t]+)?[<\"]([^>\"]+)[>\"])?"
+    )
+    .unwrap();
+}
+
+/// Include-what-you-use pragma (`// IWYU pragma: ...`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pragma {
+    /// The include is never removed
+    Keep,
+    /// The included header is provided by the including file
+    Export,
+    /// Following includes are exported
+    BeginExports,
+    EndExports,
+    /// The header should not be included directly, but the optional public header
+    Private(Option<String>),
+    /// The header should never be included by this file
+    NoInclude(String),
+}
+
+impl Pragma {
+    /// Parses the pragma in the comment of the given line
+    pub fn parse(line: &str) -> Option<Pragma> {
+        let captures = RE_PRAGMA.captures(line)?;
+        let header = captures.get(2).map(|m| m.as_str().to_string());
+        match &captures[1] {
+            "keep" => Some(Pragma::Keep),
+            "export" => Some(Pragma::Export),
+            "begin_exports" => Some(Pragma::BeginExports),
+            "end_exports" => Some(Pragma::EndExports),
+            "private" => Some(Pragma::Private(header)),
+            "no_include" => header.map(Pragma::NoInclude),
+            _ => None,
+        }
+    }
+}
+
+/// Pragmas of a file that are not bound to a single include
+#[derive(Debug, Default, Clone)]
+pub struct FilePragmas {
+    /// Line ranges (starting with 1) of the `begin_exports`..`end_exports` blocks
+    exports: Vec<(usize, usize)>,
+    /// Whether the file is private and the public header that should be included instead
+    pub private: Option<Option<String>>,
+    /// Headers that should never be included
+    pub no_include: Vec<String>,
+}
+
+impl FilePragmas {
+    pub fn parse(content: &str) -> FilePragmas {
+        let mut result = FilePragmas::default();
+        let mut begin = None;
+        for (i, line) in content.lines().enumerate() {
+            match Pragma::parse(line) {
+                Some(Pragma::BeginExports) => begin = Some(i + 1),
+                Some(Pragma::EndExports) => {
+                    if let Some(begin) = begin.take() {
+                        result.exports.push((begin, i + 1));
+                    }
+                }
+                Some(Pragma::Private(public)) => result.private = Some(public),
+                Some(Pragma::NoInclude(header)) => result.no_include.push(header),
+                _ => {}
+            }
+        }
+        if let Some(begin) = begin {
+            result.exports.push((begin, usize::MAX));
+        }
+        result
+    }
+
+    /// Returns whether the include at the given line is within an export block
+    pub fn is_exported(&self, line: usize) -> bool {
+        self.exports
+            .iter()
+            .any(|&(begin, end)| begin < line && line < end)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pragmas() {
+        assert_eq!(
+            Pragma::parse("#include \"A.hpp\" // IWYU pragma: keep"),
+            Some(Pragma::Keep)
+        );
+        assert_eq!(
+            Pragma::parse("#include <a>  //IWYU pragma: export"),
+            Some(Pragma::Export)
+        );
+        assert_eq!(Pragma::parse("#include \"A.hpp\" // keep"), None);
+
+        let pragmas = FilePragmas::parse(
+            "// IWYU pragma: private, include \"Public.hpp\"\n\
+             // IWYU pragma: no_include <vector>\n\
+             // IWYU pragma: begin_exports\n\
+             #include \"A.hpp\"\n\
+             // IWYU pragma: end_exports\n\
+             #include \"B.hpp\"\n",
+        );
+        assert_eq!(pragmas.private, Some(Some("Public.hpp".into())));
+        assert_eq!(pragmas.no_include, ["vector"]);
+        assert!(pragmas.is_exported(4));
+        assert!(!pragmas.is_exported(6));
+    }
+}
@@ -1,56 +1,55 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::Write;
 use std::path::Path;
-use std::process::Command;
-use std::sync::{Arc, RwLock};
+use std::process::{Command, Stdio};
 
-lazy_static::lazy_static! {
-    pub static ref EXEC: Arc<RwLock<String>> = Arc::new(RwLock::new(String::new()));
-}
+use super::error::{Error, Result};
 
-/// Format (sort) includes of the given file
-pub fn includes(file: &Path) -> io::Result<()> {
-    let fmt_ranges = include_ranges(file)?;
+/// Format (sort) includes of the given file content using the clang-format executable `exec`.
+///
+/// The `file` path is only used to find the style configuration.
+pub fn includes(exec: &str, file: &Path, content: &str) -> Result<String> {
+    let fmt_ranges = include_ranges(content);
+    let error = |message: String| Error::ClangFormat(file.into(), message);
 
-    match Command::new(&*EXEC.read().unwrap())
-        .arg(file)
-        .arg("-i")
+    let mut child = Command::new(exec)
+        .arg(format!("-assume-filename={}", file.to_string_lossy()))
         .arg("-sort-includes")
         .args(
             fmt_ranges
                 .iter()
                 .map(|(s, e)| format!("-lines={}:{}", s + 1, e + 1)),
         )
-        .output()
-    {
-        Ok(output) => {
-            if !output.status.success() {
-                eprintln!(
-                    "{} failed with {}",
-                    *EXEC.read().unwrap(),
-                    output.status.code().unwrap_or_default(),
-                );
-                io::stdout().write_all(&output.stdout)?;
-                io::stderr().write_all(&output.stderr)?;
-            }
-        }
-        Err(err) => {
-            eprintln!("{} failed: {}", *EXEC.read().unwrap(), err);
-        }
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| error(format!("{}: {}", exec, e)))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(content.as_bytes())
+            .map_err(|e| error(e.to_string()))?;
     }
+    let output = child.wait_with_output().map_err(|e| error(e.to_string()))?;
 
-    Ok(())
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into())
+    } else {
+        Err(error(format!(
+            "{} failed with {}: {}",
+            exec,
+            output.status.code().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
 }
 
-fn include_ranges(file: &Path) -> io::Result<Vec<(usize, usize)>> {
-    let file = File::open(file)?;
-
+fn include_ranges(content: &str) -> Vec<(usize, usize)> {
     let mut fmt_ranges = Vec::new();
     let mut start = 0;
     let mut has_preprocessor_stmt = false;
 
-    for (i, line) in BufReader::new(file).lines().enumerate() {
-        let line = line?;
+    for (i, line) in content.lines().enumerate() {
         let trimmed_line = line.trim_start();
         if trimmed_line.starts_with('#') {
             has_preprocessor_stmt = true;
@@ -62,5 +61,5 @@ fn include_ranges(file: &Path) -> io::Result<Vec<(usize, usize)>> {
             has_preprocessor_stmt = false;
         }
     }
-    Ok(fmt_ranges)
+    fmt_ranges
 }
@@ -5,6 +5,7 @@ use std::path::{Path, PathBuf};
 use serde::Deserialize;
 
 use super::dependencies::Dependencies;
+use super::error::{Error, Result};
 use super::util;
 
 #[derive(Deserialize)]
@@ -15,30 +16,35 @@ struct CompilationEntry {
 
 /// Compile commands for the sourcefiles of a project
 pub struct Compilations {
-    map: HashMap<PathBuf, String>,
+    /// All compile commands of a file (e.g. for different build configurations)
+    map: HashMap<PathBuf, Vec<String>>,
 }
 
 impl Compilations {
     /// Parse a clang compilation database and collect the compile commands
-    pub fn parse(file: &Path, filter: &regex::Regex) -> Result<Compilations, String> {
-        let file = File::open(file).map_err(|e| format!("{}", e))?;
-        let commands: Vec<CompilationEntry> =
-            serde_json::from_reader(file).map_err(|e| format!("{}", e))?;
+    /// of the files accepted by the `filter`
+    pub fn parse<F: Fn(&Path) -> bool>(path: &Path, filter: F) -> Result<Compilations> {
+        let file = File::open(path).map_err(|e| Error::Compilations(path.into(), e.to_string()))?;
+        let commands: Vec<CompilationEntry> = serde_json::from_reader(file)
+            .map_err(|e| Error::Compilations(path.into(), e.to_string()))?;
 
-        Ok(Compilations {
-            map: commands
-                .into_iter()
-                .filter(|e| filter.is_match(e.file.to_str().expect("Malformed db source")))
-                .map(|e| (e.file, e.command))
-                .collect(),
-        })
+        let mut map: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        for entry in commands {
+            if filter(&entry.file) {
+                let commands = map.entry(entry.file).or_default();
+                if !commands.contains(&entry.command) {
+                    commands.push(entry.command);
+                }
+            }
+        }
+        Ok(Compilations { map })
     }
 
     /// Collect all include paths contained in the compile commands
     pub fn collect_include_paths(&self) -> Vec<PathBuf> {
         let mut paths: HashSet<PathBuf> = HashSet::new();
 
-        for command in self.map.values() {
+        for command in self.map.values().flatten() {
             for path in util::include_paths(command) {
                 if !paths.contains(Path::new(path)) {
                     paths.insert(PathBuf::from(path));
@@ -51,25 +57,44 @@ impl Compilations {
 
     /// Retrieve the corresponding compiler arguments
     pub fn get_related_args(&self, file: &Path, index: &Dependencies) -> Option<Vec<String>> {
-        if let Some(command) = self.map.get(file) {
-            parse_args(command)
-        } else {
-            let dependencies = index.get(file);
-            // Check direct dependencies first
-            for dependency in dependencies {
-                if let Some(command) = self.map.get(dependency) {
-                    return parse_args(command);
-                }
-            }
-            // Search whole subtree
-            for dependency in dependencies {
-                if let Some(args) = self.get_related_args(dependency, index) {
-                    return Some(args);
-                }
-            }
+        self.get_related_configurations(file, index)
+            .into_iter()
+            .next()
+    }
 
-            None
+    /// Retrieve the compiler arguments of all compile commands for the file.
+    ///
+    /// Headers use the commands of the first source including them.
+    pub fn get_related_configurations(
+        &self,
+        file: &Path,
+        index: &Dependencies,
+    ) -> Vec<Vec<String>> {
+        self.get_related_source(file, index)
+            .and_then(|source| self.map.get(source))
+            .map(|commands| commands.iter().filter_map(|c| parse_args(c)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the file itself if it has compile commands,
+    /// otherwise the first source including it (directly or indirectly).
+    pub fn get_related_source<'a>(
+        &'a self,
+        file: &Path,
+        index: &'a Dependencies,
+    ) -> Option<&'a Path> {
+        if let Some((source, _)) = self.map.get_key_value(file) {
+            return Some(source);
+        }
+        let dependencies = index.get(file);
+        // Check direct dependencies first
+        if let Some(source) = dependencies.iter().find(|d| self.map.contains_key(*d)) {
+            return Some(source);
         }
+        // Search whole subtree
+        dependencies
+            .iter()
+            .find_map(|dependency| self.get_related_source(dependency, index))
     }
 
     /// Return all sources
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use super::mappings::Mappings;
+use super::util;
+
+/// Name of the configuration files, that are searched upwards from the analyzed files
+pub const CONFIG_FILE: &str = ".unused-includes.toml";
+
+/// Contents of a configuration file.
+///
+/// Unset values are inherited from the configuration files of the parent directories.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Stop searching the parent directories for further configuration files
+    pub root: bool,
+    pub filter: Option<String>,
+    pub ignore_includes: Option<String>,
+    pub clang_format: Option<String>,
+    /// Regex matching the comment after an include that should be kept
+    pub keep_comment: Option<String>,
+    pub header_extensions: Option<Vec<String>>,
+    /// Directories (like `src` or `include`) to which local includes are relative
+    pub source_roots: Option<Vec<String>>,
+    /// IWYU (`.imp`) or TOML files mapping private to public headers
+    pub mapping_files: Option<Vec<PathBuf>>,
+    /// Rules for the headers that correspond to a sourcefile
+    pub associated_headers: Option<Vec<Association>>,
+    /// Report sourcefiles whose associated header is not the first include
+    pub associated_header_first: Option<bool>,
+}
+
+/// Associates a sourcefile with its header (e.g. `FooTest.cpp` with `Foo.hpp`).
+///
+/// The `source` regex is matched against the file stem of the sourcefile,
+/// its captures can be used in the `header` stem (like `$1`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Association {
+    pub source: String,
+    pub header: String,
+}
+
+impl ConfigFile {
+    fn load(path: &Path) -> Option<ConfigFile> {
+        let content = fs::read_to_string(path).ok()?;
+        match toml::from_str::<ConfigFile>(&content) {
+            Ok(mut config) => {
+                // Relative to the config file
+                if let (Some(files), Some(dir)) = (&mut config.mapping_files, path.parent()) {
+                    for file in files {
+                        *file = dir.join(&*file);
+                    }
+                }
+                Some(config)
+            }
+            Err(err) => {
+                warn!("Invalid config {}: {}", path.to_string_lossy(), err);
+                None
+            }
+        }
+    }
+
+    /// Overwrites the values that are set in `other`
+    fn merge(&mut self, other: &ConfigFile) {
+        fn set<T: Clone>(value: &mut Option<T>, other: &Option<T>) {
+            if other.is_some() {
+                value.clone_from(other);
+            }
+        }
+        set(&mut self.filter, &other.filter);
+        set(&mut self.ignore_includes, &other.ignore_includes);
+        set(&mut self.clang_format, &other.clang_format);
+        set(&mut self.keep_comment, &other.keep_comment);
+        set(&mut self.header_extensions, &other.header_extensions);
+        set(&mut self.source_roots, &other.source_roots);
+        set(&mut self.mapping_files, &other.mapping_files);
+        set(&mut self.associated_headers, &other.associated_headers);
+        set(
+            &mut self.associated_header_first,
+            &other.associated_header_first,
+        );
+    }
+}
+
+/// Resolved settings for a file
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub filter: Regex,
+    pub ignore_includes: Regex,
+    pub clang_format: String,
+    pub keep_comment: Regex,
+    pub header_extensions: Vec<String>,
+    pub source_roots: Vec<String>,
+    pub mappings: Arc<Mappings>,
+    /// Source stem regex and the header stem with the captures
+    pub associated_headers: Vec<(Regex, String)>,
+    pub associated_header_first: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            filter: Regex::new(".").unwrap(),
+            ignore_includes: Regex::new("(/private/|[_/]impl[_\\./])").unwrap(),
+            clang_format: "clang-format".into(),
+            keep_comment: Regex::new("^[ \\t]*//[ \\t]*keep").unwrap(),
+            header_extensions: vec!["h".into(), "hpp".into()],
+            source_roots: vec![
+                "src".into(),
+                "include".into(),
+                "src/main".into(),
+                "include/main".into(),
+            ],
+            mappings: Arc::default(),
+            associated_headers: vec![(Regex::new("^(.*)$").unwrap(), "$1".into())],
+            associated_header_first: false,
+        }
+    }
+}
+
+impl Settings {
+    fn new(config: &ConfigFile, mappings: Arc<Mappings>) -> Settings {
+        fn regex(value: &Option<String>, default: Regex) -> Regex {
+            match value.as_deref().map(Regex::new) {
+                Some(Ok(regex)) => regex,
+                Some(Err(err)) => {
+                    warn!("Invalid regex in config: {}", err);
+                    default
+                }
+                None => default,
+            }
+        }
+
+        let default = Settings::default();
+        Settings {
+            filter: regex(&config.filter, default.filter),
+            ignore_includes: regex(&config.ignore_includes, default.ignore_includes),
+            clang_format: config.clang_format.clone().unwrap_or(default.clang_format),
+            keep_comment: regex(&config.keep_comment, default.keep_comment),
+            header_extensions: config
+                .header_extensions
+                .clone()
+                .unwrap_or(default.header_extensions),
+            source_roots: config.source_roots.clone().unwrap_or(default.source_roots),
+            mappings,
+            associated_headers: match &config.associated_headers {
+                Some(associations) => associations
+                    .iter()
+                    .filter_map(|a| match Regex::new(&a.source) {
+                        Ok(regex) => Some((regex, a.header.clone())),
+                        Err(err) => {
+                            warn!("Invalid regex in config: {}", err);
+                            None
+                        }
+                    })
+                    .collect(),
+                None => default.associated_headers,
+            },
+            associated_header_first: config
+                .associated_header_first
+                .unwrap_or(default.associated_header_first),
+        }
+    }
+
+    /// Returns whether the given path points to a header file
+    pub fn is_header_file(&self, path: &Path) -> bool {
+        util::is_header_file(path, &self.header_extensions)
+    }
+
+    /// Returns whether the `header` corresponds to the `source` file
+    pub fn is_associated_header(&self, source: &Path, header: &Path) -> bool {
+        let (source, header) = match (source.file_stem(), header.file_stem()) {
+            (Some(source), Some(header)) => (source.to_string_lossy(), header.to_string_lossy()),
+            _ => return false,
+        };
+        self.associated_headers.iter().any(|(regex, replacement)| {
+            regex.captures(&source).is_some_and(|captures| {
+                let mut expected = String::new();
+                captures.expand(replacement, &mut expected);
+                expected == header
+            })
+        })
+    }
+}
+
+/// Discovers the configuration files and resolves the settings of the files.
+///
+/// The configuration files of nested directories overwrite those of their parents
+/// and the `overrides` (command line) overwrite all of them.
+pub struct Config {
+    overrides: ConfigFile,
+    /// Merged configurations of the directories
+    dirs: Mutex<HashMap<PathBuf, Arc<(ConfigFile, Settings)>>>,
+    /// Loaded mapping files, that are shared by the directories
+    mappings: Mutex<HashMap<Vec<PathBuf>, Arc<Mappings>>>,
+}
+
+impl Config {
+    pub fn new(overrides: ConfigFile) -> Config {
+        Config {
+            overrides,
+            dirs: Mutex::new(HashMap::new()),
+            mappings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the settings for the given file or directory
+    pub fn settings(&self, path: &Path) -> Settings {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        };
+        self.dir(dir).1.clone()
+    }
+
+    /// Returns whether the given file matches the filter of its directory
+    pub fn is_selected(&self, path: &Path) -> bool {
+        self.settings(path).filter.is_match(&path.to_string_lossy())
+    }
+
+    fn dir(&self, dir: &Path) -> Arc<(ConfigFile, Settings)> {
+        if let Some(entry) = self.dirs.lock().unwrap().get(dir) {
+            return entry.clone();
+        }
+
+        let file = ConfigFile::load(&dir.join(CONFIG_FILE));
+        let mut config = match (&file, dir.parent()) {
+            (Some(file), _) if file.root => ConfigFile::default(),
+            (_, Some(parent)) if parent != dir => self.dir(parent).0.clone(),
+            _ => ConfigFile::default(),
+        };
+        if let Some(file) = &file {
+            config.merge(file);
+        }
+
+        let mut resolved = config.clone();
+        resolved.merge(&self.overrides);
+        let mappings = self.mappings(resolved.mapping_files.as_deref().unwrap_or_default());
+        let entry = Arc::new((config, Settings::new(&resolved, mappings)));
+
+        self.dirs.lock().unwrap().insert(dir.into(), entry.clone());
+        entry
+    }
+
+    fn mappings(&self, files: &[PathBuf]) -> Arc<Mappings> {
+        let mut cache = self.mappings.lock().unwrap();
+        if let Some(mappings) = cache.get(files) {
+            return mappings.clone();
+        }
+
+        let mut mappings = Mappings::default();
+        for file in files {
+            match Mappings::load(file) {
+                Ok(loaded) => mappings.extend(loaded),
+                Err(err) => warn!("Invalid mapping file {}: {}", file.to_string_lossy(), err),
+            }
+        }
+        // The mapping files take precedence
+        mappings.extend(Mappings::stdlib());
+        let mappings = Arc::new(mappings);
+        cache.insert(files.to_vec(), mappings.clone());
+        mappings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::current_dir;
+
+    #[test]
+    fn test_config() {
+        let dir = current_dir().unwrap().join("tests/src");
+
+        let config = Config::new(ConfigFile::default());
+        let settings = config.settings(&dir.join("Main.cpp"));
+        assert_eq!(settings.keep_comment.as_str(), "^[ \\t]*//[ \\t]*keep");
+        assert!(settings.ignore_includes.is_match("/src/Ignore_impl.hpp"));
+        assert_eq!(settings.header_extensions, ["h", "hh", "hpp"]);
+
+        // Nested config overwrites the parent config
+        let settings = config.settings(&dir.join("config/Selected.cpp"));
+        assert_eq!(
+            settings.keep_comment.as_str(),
+            "^[ \\t]*//[ \\t]*(keep|export)"
+        );
+        assert_eq!(settings.header_extensions, ["h", "hh", "hpp"]);
+
+        // The filter is resolved per directory
+        assert!(config.is_selected(&dir.join("Main.cpp")));
+        assert!(config.is_selected(&dir.join("config/Selected.cpp")));
+        assert!(!config.is_selected(&dir.join("config/Other.cpp")));
+
+        // Command line overwrites all configs
+        let config = Config::new(ConfigFile {
+            keep_comment: Some("NOLINT".into()),
+            ..ConfigFile::default()
+        });
+        let settings = config.settings(&dir.join("config/Selected.cpp"));
+        assert_eq!(settings.keep_comment.as_str(), "NOLINT");
+    }
+
+    #[test]
+    fn test_associated_headers() {
+        let settings = Settings::default();
+        assert!(settings.is_associated_header(Path::new("src/Foo.cpp"), Path::new("inc/Foo.hpp")));
+        assert!(!settings.is_associated_header(Path::new("FooTest.cpp"), Path::new("Foo.hpp")));
+
+        let config = Config::new(ConfigFile {
+            associated_headers: Some(vec![
+                Association {
+                    source: "^(.*)Test$".into(),
+                    header: "$1".into(),
+                },
+                Association {
+                    source: "^(.*)_impl$".into(),
+                    header: "$1".into(),
+                },
+            ]),
+            ..ConfigFile::default()
+        });
+        let settings = config.settings(&current_dir().unwrap());
+        assert!(settings.is_associated_header(Path::new("FooTest.cpp"), Path::new("Foo.hpp")));
+        assert!(settings.is_associated_header(Path::new("foo_impl.cc"), Path::new("foo.h")));
+        assert!(!settings.is_associated_header(Path::new("foo_impl.cc"), Path::new("bar.h")));
+    }
+}
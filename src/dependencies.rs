@@ -1,9 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::path::{Path, PathBuf};
 
 use multimap::MultiMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use super::config::Config;
 use super::fileio;
 use super::util;
 
@@ -11,6 +12,12 @@ pub struct Dependencies {
     index: MultiMap<PathBuf, PathBuf>,
 }
 
+impl Default for Dependencies {
+    fn default() -> Dependencies {
+        Dependencies::new()
+    }
+}
+
 impl Dependencies {
     pub fn new() -> Dependencies {
         Dependencies {
@@ -19,14 +26,16 @@ impl Dependencies {
     }
 
     /// Creates an index with all sources and their dependencies (sources that include them).
-    pub fn create(files: &[&Path], directories: &[PathBuf], filter: &regex::Regex) -> Dependencies {
+    ///
+    /// Only files matching the filter of their directory are indexed.
+    pub fn create(files: &[&Path], directories: &[PathBuf], config: &Config) -> Dependencies {
         let mut dependencies = Dependencies {
             index: MultiMap::new(),
         };
 
         for file in files {
-            if filter.is_match(file.to_str().unwrap()) {
-                dependencies.add(file.as_ref(), directories);
+            if config.is_selected(file) {
+                dependencies.add(file.as_ref(), directories, config);
             }
         }
 
@@ -34,8 +43,8 @@ impl Dependencies {
             if let Ok(read_dir) = util::read_dir_rec(dir) {
                 for path in read_dir {
                     let path = path.unwrap().path();
-                    if filter.is_match(path.to_str().unwrap()) && util::is_header_file(&path) {
-                        dependencies.add(&path, directories);
+                    if config.is_selected(&path) && config.settings(&path).is_header_file(&path) {
+                        dependencies.add(&path, directories, config);
                     }
                 }
             }
@@ -44,16 +53,22 @@ impl Dependencies {
         dependencies
     }
 
-    pub fn add(&mut self, file: &Path, include_paths: &[PathBuf]) {
-        for include in fileio::parse_includes(file) {
-            if let Some(include) = util::find_include(file, Path::new(&include), include_paths) {
+    pub fn add(&mut self, file: &Path, include_paths: &[PathBuf], config: &Config) {
+        let settings = config.settings(file);
+        for include in fileio::parse_includes(file, settings.is_header_file(file)) {
+            if let Some(include) = util::find_include(
+                file,
+                Path::new(&include),
+                include_paths,
+                &settings.source_roots,
+            ) {
                 if let Ok(include) = include.canonicalize() {
                     if let Ok(file) = file.canonicalize() {
                         self.index.insert(include, file);
                     }
                 }
             } else {
-                eprintln!("Missing include {} {:?}", include, file);
+                warn!("Missing include {} {:?}", include, file);
             }
         }
     }
@@ -66,6 +81,49 @@ impl Dependencies {
         }
     }
 
+    /// Returns the given `files` together with all indexed files.
+    ///
+    /// Every file is returned once and included files precede the files including them.
+    pub fn ordered(&self, files: &[&Path]) -> Vec<PathBuf> {
+        let mut includes: MultiMap<&Path, &Path> = MultiMap::new();
+        let mut all = BTreeSet::new();
+        for (include, dependencies) in self.index.iter_all() {
+            all.insert(include.as_path());
+            for dependency in dependencies {
+                all.insert(dependency.as_path());
+                includes.insert(dependency.as_path(), include.as_path());
+            }
+        }
+        let canonical = files
+            .iter()
+            .filter_map(|f| f.canonicalize().ok())
+            .collect::<Vec<_>>();
+        all.extend(canonical.iter().map(PathBuf::as_path));
+
+        let mut visited = HashSet::new();
+        let mut result = Vec::with_capacity(all.len());
+        for file in all {
+            Self::ordered_impl(file, &includes, &mut visited, &mut result);
+        }
+        result
+    }
+
+    fn ordered_impl<'a>(
+        file: &'a Path,
+        includes: &MultiMap<&'a Path, &'a Path>,
+        visited: &mut HashSet<&'a Path>,
+        result: &mut Vec<PathBuf>,
+    ) {
+        if visited.insert(file) {
+            if let Some(children) = includes.get_vec(file) {
+                for child in children {
+                    Self::ordered_impl(child, includes, visited, result);
+                }
+            }
+            result.push(file.into());
+        }
+    }
+
     /// Print the dependency tree with the given `root` file
     pub fn print(&self, root: &Path) {
         let mut visited = HashSet::new();
@@ -73,19 +131,18 @@ impl Dependencies {
     }
 
     fn print_impl<'a>(&'a self, root: &'a Path, indent: usize, visited: &mut HashSet<&'a Path>) {
-        if indent > 0 {
-            for _ in 1..indent {
-                print!("    ");
-            }
-            print!("  - ");
-        }
+        let prefix = if indent > 0 {
+            format!("{}  - ", "    ".repeat(indent - 1))
+        } else {
+            String::new()
+        };
         if visited.insert(root) {
-            println!("{}", root.to_string_lossy());
+            info!("{}{}", prefix, root.to_string_lossy());
             for child in self.get(root) {
                 self.print_impl(child, indent + 1, visited);
             }
         } else {
-            println!("!circular: {}", root.to_string_lossy());
+            info!("{}!circular: {}", prefix, root.to_string_lossy());
         }
     }
 }
@@ -109,3 +166,31 @@ impl<'a> Deserialize<'a> for Dependencies {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ordered() {
+        let mut dependencies = Dependencies::new();
+        dependencies.index.insert("/b.hpp".into(), "/a.hpp".into());
+        dependencies
+            .index
+            .insert("/a.hpp".into(), "/main.cpp".into());
+        dependencies
+            .index
+            .insert("/b.hpp".into(), "/main.cpp".into());
+        dependencies.index.insert("/c.hpp".into(), "/b.hpp".into());
+
+        assert_eq!(
+            dependencies.ordered(&[]),
+            vec![
+                PathBuf::from("/c.hpp"),
+                PathBuf::from("/b.hpp"),
+                PathBuf::from("/a.hpp"),
+                PathBuf::from("/main.cpp"),
+            ]
+        );
+    }
+}
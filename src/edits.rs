@@ -0,0 +1,412 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env::current_dir;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use clang::Unsaved;
+use similar::{DiffOp, TextDiff};
+
+use super::analyze::Include;
+use super::clangfmt;
+use super::config::Settings;
+use super::error::{Error, Result};
+use super::fileio::{self, IncludeStatement};
+
+/// A single modification of a file
+#[derive(Debug, Clone)]
+pub enum Edit {
+    /// Removal of an unused include
+    Remove(Include),
+    /// Include propagated from a dependency
+    Add(IncludeStatement),
+    /// Forward declaration replacing an include
+    Declare(String),
+}
+
+/// Maps the lines of the modified content of a file to its original lines
+pub struct LineMap(Option<Vec<Option<usize>>>);
+
+impl LineMap {
+    /// Returns the original line of the given `line` (starting with 1)
+    /// or `None` if the line was added.
+    pub fn get(&self, line: usize) -> Option<usize> {
+        match &self.0 {
+            Some(lines) => lines.get(line.checked_sub(1)?).copied().flatten(),
+            None => Some(line),
+        }
+    }
+
+    /// Returns the original line of the given `line` (starting with 1).
+    ///
+    /// Added lines are located before the original line following them.
+    pub fn position(&self, line: usize) -> usize {
+        let lines = match &self.0 {
+            Some(lines) => lines,
+            None => return line,
+        };
+        self.get(line).unwrap_or_else(|| {
+            lines
+                .iter()
+                .take(line.saturating_sub(1))
+                .rev()
+                .find_map(|l| *l)
+                .map_or(1, |previous| previous + 1)
+        })
+    }
+}
+
+/// Returns the original line number (starting with 1) for each line of the `content`
+fn original_lines(original: &str, content: &str) -> Vec<Option<usize>> {
+    let diff = TextDiff::from_lines(original, content);
+    let mut lines = vec![None; content.split_inclusive('\n').count()];
+    for op in diff.ops() {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = *op
+        {
+            for i in 0..len {
+                lines[new_index + i] = Some(old_index + i + 1);
+            }
+        }
+    }
+    lines
+}
+
+struct FileEdits {
+    original: String,
+    content: String,
+    edits: Vec<Edit>,
+}
+
+/// Records the edits of all files.
+///
+/// Unless in dry-run mode, the edits are immediately written back to the files.
+/// Otherwise the modified contents are only kept in memory and provided
+/// as unsaved files to the following analyses.
+pub struct Edits {
+    dry_run: bool,
+    files: BTreeMap<PathBuf, FileEdits>,
+}
+
+impl Edits {
+    pub fn new(dry_run: bool) -> Edits {
+        Edits {
+            dry_run,
+            files: BTreeMap::new(),
+        }
+    }
+
+    fn entry(&mut self, file: &Path) -> Result<&mut FileEdits> {
+        if !self.files.contains_key(file) {
+            let original = fs::read_to_string(file).map_err(|e| Error::Io(file.into(), e))?;
+            self.files.insert(
+                file.into(),
+                FileEdits {
+                    content: original.clone(),
+                    original,
+                    edits: Vec::new(),
+                },
+            );
+        }
+        Ok(self.files.get_mut(file).unwrap())
+    }
+
+    fn write(&self, file: &Path) -> Result<()> {
+        if !self.dry_run {
+            if let Some(entry) = self.files.get(file) {
+                // Symlinks are kept, their target is replaced
+                let target = fs::canonicalize(file).map_err(|e| Error::Io(file.into(), e))?;
+                let mut name = target.file_name().unwrap_or_default().to_os_string();
+                name.push(".tmp");
+                let temppath = target.with_file_name(name);
+                // Replace the file at once, so that concurrent parsers never see partial writes
+                fs::write(&temppath, &entry.content)
+                    .and_then(|_| {
+                        fs::set_permissions(&temppath, fs::metadata(&target)?.permissions())
+                    })
+                    .and_then(|_| fs::rename(&temppath, &target))
+                    .map_err(|e| {
+                        let _ = fs::remove_file(&temppath);
+                        Error::Io(file.into(), e)
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes all modified files to disk and leaves the dry-run mode.
+    pub fn apply(&mut self) -> Result<()> {
+        self.dry_run = false;
+        for (file, entry) in &self.files {
+            if entry.content != entry.original {
+                self.write(file)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of edits applied to the `file` so far.
+    ///
+    /// This can be used to detect whether a file has changed since it was analyzed.
+    pub fn version(&self, file: &Path) -> usize {
+        self.files.get(file).map_or(0, |entry| entry.edits.len())
+    }
+
+    /// Returns the [`Edits::version`] of all modified files
+    pub fn versions(&self) -> HashMap<PathBuf, usize> {
+        self.files
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.edits.len()))
+            .collect()
+    }
+
+    /// Returns the mapping of the current lines of the `file` to its original lines.
+    ///
+    /// The findings of an analysis refer to the current content, which differs from the
+    /// original content on disk in dry-run mode.
+    pub fn line_map(&self, file: &Path) -> LineMap {
+        LineMap(
+            self.files
+                .get(file)
+                .filter(|entry| entry.content != entry.original)
+                .map(|entry| original_lines(&entry.original, &entry.content)),
+        )
+    }
+
+    /// Replaces the content of the `file` and records the `edits`.
+    ///
+    /// On failure the file remains unchanged.
+    fn update(&mut self, file: &Path, content: String, edits: Vec<Edit>) -> Result<()> {
+        let entry = self.entry(file)?;
+        let previous = std::mem::replace(&mut entry.content, content);
+
+        if let Err(err) = self.write(file) {
+            self.entry(file)?.content = previous;
+            return Err(err);
+        }
+        self.entry(file)?.edits.extend(edits);
+        Ok(())
+    }
+
+    /// Removes the given `includes` from the `file` and sorts the remaining ones.
+    pub fn remove_includes(
+        &mut self,
+        file: &Path,
+        settings: &Settings,
+        includes: &[Include],
+    ) -> Result<()> {
+        if includes.is_empty() {
+            return Ok(());
+        }
+
+        let entry = self.entry(file)?;
+        let lines = includes.iter().map(|i| i.line).collect::<Vec<_>>();
+        let content = fileio::remove_includes(&entry.content, &lines);
+        // Sort includes, the removal is still valid without formatting
+        let content = match clangfmt::includes(&settings.clang_format, file, &content) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                warn!("{}", err);
+                content
+            }
+        };
+
+        self.update(
+            file,
+            content,
+            includes.iter().cloned().map(Edit::Remove).collect(),
+        )
+    }
+
+    /// Adds the `includes` to the `file` if they are not already included.
+    ///
+    /// Returns the includes that were actually added.
+    pub fn add_includes<I>(
+        &mut self,
+        file: &Path,
+        settings: &Settings,
+        includes: I,
+    ) -> Result<Vec<IncludeStatement>>
+    where
+        I: Iterator<Item = IncludeStatement>,
+    {
+        let entry = self.entry(file)?;
+        let (content, added) =
+            fileio::add_includes(&entry.content, settings.is_header_file(file), includes);
+        if added.is_empty() {
+            return Ok(added);
+        }
+
+        self.update(
+            file,
+            content,
+            added.iter().cloned().map(Edit::Add).collect(),
+        )?;
+        Ok(added)
+    }
+
+    /// Adds the forward `declarations` to the `file` after its includes.
+    pub fn add_declarations<I>(
+        &mut self,
+        file: &Path,
+        settings: &Settings,
+        declarations: I,
+    ) -> Result<()>
+    where
+        I: Iterator<Item = String>,
+    {
+        let entry = self.entry(file)?;
+        let (content, added) =
+            fileio::add_declarations(&entry.content, settings.is_header_file(file), declarations);
+        if added.is_empty() {
+            return Ok(());
+        }
+
+        self.update(
+            file,
+            content,
+            added.into_iter().map(Edit::Declare).collect(),
+        )
+    }
+
+    /// Returns the modified contents that have not been written to disk.
+    pub fn unsaved(&self) -> Vec<Unsaved> {
+        if self.dry_run {
+            self.files
+                .iter()
+                .filter(|(_, entry)| entry.content != entry.original)
+                .map(|(path, entry)| Unsaved::new(path, &entry.content))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns whether any file would be changed
+    pub fn is_empty(&self) -> bool {
+        self.files.values().all(|entry| entry.edits.is_empty())
+    }
+
+    /// Print all planned edits
+    pub fn print(&self) {
+        for (path, entry) in &self.files {
+            if entry.edits.is_empty() {
+                continue;
+            }
+            info!("{}:", path.to_string_lossy());
+            for edit in &entry.edits {
+                match edit {
+                    Edit::Remove(include) => {
+                        info!("  - {} (line {})", include.name, include.line)
+                    }
+                    Edit::Add(include) => info!("  + {}", include),
+                    Edit::Declare(declaration) => info!("  + {}", declaration),
+                }
+            }
+        }
+    }
+
+    /// Writes all changes as a single unified diff that can be applied with `git apply`.
+    ///
+    /// The file paths are relative to the current working directory if possible.
+    pub fn diff<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let cwd = current_dir()?.canonicalize()?;
+        for (path, entry) in &self.files {
+            if entry.content == entry.original {
+                continue;
+            }
+            let (old, new) = diff_paths(path, &cwd);
+            let diff = TextDiff::from_lines(&entry.original, &entry.content);
+            write!(out, "{}", diff.unified_diff().header(&old, &new))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the old and new path of the diff header of the (canonical) `path`.
+///
+/// Files outside of the working directory keep their absolute path without prefixes.
+fn diff_paths(path: &Path, cwd: &Path) -> (String, String) {
+    match path.strip_prefix(cwd) {
+        Ok(relative) => {
+            let relative = relative.to_string_lossy();
+            (format!("a/{}", relative), format!("b/{}", relative))
+        }
+        Err(_) => {
+            let path = path.to_string_lossy();
+            (path.to_string(), path.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_symlink() {
+        let dir = std::env::temp_dir().join(format!("unused-includes-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("Target.hpp");
+        let link = dir.join("Link.hpp");
+        fs::write(&target, "#pragma once\n").unwrap();
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let mut edits = Edits::new(false);
+        edits
+            .add_includes(
+                &link,
+                &Settings::default(),
+                vec![IncludeStatement::Local("A.hpp".into())].into_iter(),
+            )
+            .unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::read_to_string(&target)
+            .unwrap()
+            .contains("#include \"A.hpp\""));
+        assert!(!dir.join("Target.hpp.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff_paths() {
+        let cwd = Path::new("/project");
+        assert_eq!(
+            diff_paths(Path::new("/project/src/A.hpp"), cwd),
+            ("a/src/A.hpp".to_string(), "b/src/A.hpp".to_string())
+        );
+        assert_eq!(
+            diff_paths(Path::new("/usr/include/B.hpp"), cwd),
+            (
+                "/usr/include/B.hpp".to_string(),
+                "/usr/include/B.hpp".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_original_lines() {
+        let original = "#include <a>\n#include <b>\n\nint x;\n";
+        let content = "#include <a>\n#include <c>\n\nint x;\n";
+        assert_eq!(
+            original_lines(original, content),
+            vec![Some(1), None, Some(3), Some(4)]
+        );
+
+        let lines = LineMap(Some(original_lines(original, content)));
+        assert_eq!(lines.get(2), None);
+        assert_eq!(lines.get(4), Some(4));
+        assert_eq!(LineMap(None).get(2), Some(2));
+        assert_eq!(lines.position(2), 2);
+        assert_eq!(lines.position(4), 4);
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Errors of the analysis and the file modifications
+#[derive(Debug)]
+pub enum Error {
+    /// The compilation database could not be read or parsed
+    Compilations(PathBuf, String),
+    /// The dependency index could not be read or parsed
+    Index(PathBuf, String),
+    /// No compiler args found in the compilation database
+    MissingArgs(PathBuf),
+    /// libclang could not parse the file
+    Parse(PathBuf, String),
+    /// Reading or writing the file failed
+    Io(PathBuf, io::Error),
+    /// clang-format failed to sort the includes
+    ClangFormat(PathBuf, String),
+    /// The include is not directly included by the file
+    NotIncluded(PathBuf, String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Short identifier of the error kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Compilations(..) => "compilations",
+            Error::Index(..) => "index",
+            Error::MissingArgs(..) => "missing_args",
+            Error::Parse(..) => "parse",
+            Error::Io(..) => "io",
+            Error::ClangFormat(..) => "clang_format",
+            Error::NotIncluded(..) => "not_included",
+        }
+    }
+
+    /// The file that caused the error
+    pub fn file(&self) -> &Path {
+        match self {
+            Error::Compilations(file, _)
+            | Error::Index(file, _)
+            | Error::MissingArgs(file)
+            | Error::Parse(file, _)
+            | Error::Io(file, _)
+            | Error::ClangFormat(file, _)
+            | Error::NotIncluded(file, _) => file,
+        }
+    }
+
+    /// Whether the file could not be analyzed at all
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, Error::MissingArgs(_) | Error::Parse(..))
+    }
+
+    /// Description of the error without the file
+    pub fn message(&self) -> String {
+        match self {
+            Error::Compilations(_, err) => format!("Error parsing compilation database: {}", err),
+            Error::Index(_, err) => format!("Error parsing include index: {}", err),
+            Error::MissingArgs(_) => "Missing compiler args in compilation database".into(),
+            Error::Parse(_, err) => format!("Parsing error: {}", err),
+            Error::Io(_, err) => format!("I/O error: {}", err),
+            Error::ClangFormat(_, err) => format!("clang-format failed: {}", err),
+            Error::NotIncluded(_, include) => format!("'{}' is not included directly", include),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file().to_string_lossy(), self.message())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Error", 3)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("file", self.file())?;
+        state.serialize_field("message", &self.message())?;
+        state.end()
+    }
+}
@@ -1,31 +1,50 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::fs;
 use std::path::Path;
-use std::usize;
 
 use regex::Regex;
 
-use super::util;
-
 // Regexes for several preprocessor directives
 lazy_static::lazy_static! {
-    static ref RE_INCLUDE: Regex =
-        Regex::new("^[ \\t]*#[ \\t]*include[ \\t]*[<\"]([\\./\\w-]+)[>\"]").unwrap();
-    static ref RE_LOCAL_INCLUDE: Regex =
-        Regex::new("^[ \\t]*#[ \\t]*include[ \\t]*\"([\\./\\w-]+)\"").unwrap();
+    // `#include <global>`, `#include "local"` or `#include MACRO`, also with `include_next` and `import`
+    static ref RE_INCLUDE: Regex = Regex::new(
+        "^[ \\t]*#[ \\t]*(?:include_next|include|import)[ \\t]*(?:<([^>\\n]+)>|\"([^\"\\n]+)\"|([A-Za-z_]\\w*))"
+    )
+    .unwrap();
+    static ref RE_LOCAL_INCLUDE: Regex = Regex::new(
+        "^[ \\t]*#[ \\t]*(?:include_next|include|import)[ \\t]*\"([^\"\\n]+)\""
+    )
+    .unwrap();
     static ref RE_IF: Regex = Regex::new("^[ \\t]*#[ \\t]*if").unwrap();
     static ref RE_ENDIF: Regex = Regex::new("^[ \\t]*#[ \\t]*endif").unwrap();
     static ref RE_PRAGMA_ONCE: Regex = Regex::new("^[ \\t]*#[ \\t]*pragma[ \\t]+once").unwrap();
+    static ref RE_DEFINE: Regex = Regex::new("^[ \\t]*#[ \\t]*define").unwrap();
+    static ref RE_CONDITION: Regex = Regex::new(
+        "^[ \\t]*#[ \\t]*(?:if|ifdef|ifndef|elif|elifdef|elifndef)\\b([^\\n]*)"
+    )
+    .unwrap();
+    static ref RE_IDENTIFIER: Regex = Regex::new("[A-Za-z_]\\w*").unwrap();
+    static ref RE_HAS_INCLUDE: Regex =
+        Regex::new("__has_include(?:_next)?[ \\t]*\\([ \\t]*[<\"][^>\"]*[>\"][ \\t]*\\)").unwrap();
 }
 
+/// Identifiers of preprocessor conditions that are no macros
+const CONDITION_KEYWORDS: &[&str] = &[
+    "defined",
+    "true",
+    "false",
+    "__has_attribute",
+    "__has_cpp_attribute",
+    "__has_builtin",
+    "__has_feature",
+    "__has_extension",
+];
+
 /// Collect includes ignoring those defined in #if..#endif blocks.
-pub fn parse_includes(path: &Path) -> HashSet<String> {
-    let is_header = util::is_header_file(path);
-    if let Ok(mut file) = File::open(path) {
-        let (_, incudes) =
-            parse_includes_file(&mut file, &RE_LOCAL_INCLUDE, is_header).unwrap_or_default();
+pub fn parse_includes(path: &Path, is_header: bool) -> HashSet<String> {
+    if let Ok(buffer) = fs::read_to_string(path) {
+        let (_, incudes) = parse_includes_str(&buffer, &RE_LOCAL_INCLUDE, is_header);
         incudes
     } else {
         HashSet::new()
@@ -35,20 +54,17 @@ pub fn parse_includes(path: &Path) -> HashSet<String> {
 /// Collect includes ignoring those defined in #if..#endif blocks.
 ///
 /// Also return the offset to the first (or second if its a sourcefile) include.
-fn parse_includes_file(
-    file: &mut File,
+fn parse_includes_str(
+    buffer: &str,
     include_re: &regex::Regex,
     is_header: bool,
-) -> io::Result<(usize, HashSet<String>)> {
+) -> (usize, HashSet<String>) {
     let (mut depth, mut skip_first) = if is_header {
         (-1, false) // header guards
     } else {
         (0, true)
     };
 
-    let mut buffer = String::new();
-    file.read_to_string(&mut buffer)?;
-
     let mut offset = 0;
     let mut found = false;
     let mut includes = HashSet::new();
@@ -64,8 +80,11 @@ fn parse_includes_file(
             } else {
                 found = true;
             }
-            let include = &include_re.captures(line).unwrap()[1];
-            includes.insert(String::from(include));
+            // Computed includes have no static name
+            let captures = include_re.captures(line).unwrap();
+            if let Some(include) = captures.get(1).or_else(|| captures.get(2)) {
+                includes.insert(String::from(include.as_str()));
+            }
         }
         if !found {
             offset += line.len() + 1;
@@ -75,16 +94,17 @@ fn parse_includes_file(
         offset = 0;
     }
 
-    Ok((offset, includes))
+    (offset, includes)
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IncludeStatement {
     Local(String),
     Global(String),
 }
 
 impl IncludeStatement {
-    fn path(&self) -> &str {
+    pub fn path(&self) -> &str {
         match self {
             IncludeStatement::Local(path) => path,
             IncludeStatement::Global(path) => path,
@@ -101,52 +121,244 @@ impl fmt::Display for IncludeStatement {
     }
 }
 
-/// Adds the given `includes` in front of the old includes of the given file
-pub fn add_includes<I>(filepath: &Path, includes: I) -> io::Result<()>
+/// Adds the given `includes` in front of the old includes of the given file content.
+///
+/// Returns the new content and the includes that were not already present.
+pub fn add_includes<I>(
+    content: &str,
+    is_header: bool,
+    includes: I,
+) -> (String, Vec<IncludeStatement>)
 where
     I: Iterator<Item = IncludeStatement>,
 {
-    let mut file = OpenOptions::new().read(true).write(true).open(&filepath)?;
+    let (offset, old_includes) = parse_includes_str(content, &RE_INCLUDE, is_header);
+    let offset = offset.min(content.len());
+
+    let mut added = Vec::new();
+    let mut result = String::with_capacity(content.len());
+    result.push_str(&content[..offset]);
+    for include in includes {
+        if !old_includes.contains(include.path()) && !added.contains(&include) {
+            result.push_str(&format!("{}\n", include));
+            added.push(include);
+        }
+    }
+    result.push_str(&content[offset..]);
 
-    let (offset, old_includes) =
-        parse_includes_file(&mut file, &RE_INCLUDE, util::is_header_file(filepath))?;
+    (result, added)
+}
 
-    file.seek(SeekFrom::Start(offset as u64))?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    file.seek(SeekFrom::Start(offset as u64))?;
+/// Returns the offset after the last include, that is not within an #if..#endif block.
+///
+/// Without includes, this is the offset after the header guard.
+fn includes_end(buffer: &str, is_header: bool) -> usize {
+    let mut depth = if is_header { -1 } else { 0 };
+    let mut offset = 0;
+    let mut end = 0;
 
-    for include in includes {
-        if !old_includes.contains(include.path()) {
-            writeln!(file, "{}", include)?;
+    for line in buffer.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if RE_PRAGMA_ONCE.is_match(line) || RE_IF.is_match(line) {
+            depth += 1;
+            if depth == 0 && end == 0 {
+                end = offset;
+            }
+        } else if RE_ENDIF.is_match(line) {
+            depth -= 1;
+        } else if depth == 0
+            && (RE_INCLUDE.is_match(line) || (end == start && RE_DEFINE.is_match(line)))
+        {
+            end = offset;
         }
     }
-    file.write_all(&buffer)?;
+    end
+}
+
+/// Adds the forward `declarations` after the includes of the given file content.
+///
+/// Returns the new content and the declarations that were not already present.
+pub fn add_declarations<I>(content: &str, is_header: bool, declarations: I) -> (String, Vec<String>)
+where
+    I: Iterator<Item = String>,
+{
+    let mut added = Vec::new();
+    for declaration in declarations {
+        if !content.lines().any(|l| l.trim() == declaration) && !added.contains(&declaration) {
+            added.push(declaration);
+        }
+    }
+    if added.is_empty() {
+        return (content.into(), added);
+    }
 
-    Ok(())
+    let offset = includes_end(content, is_header);
+    let mut result = String::with_capacity(content.len());
+    result.push_str(&content[..offset]);
+    if offset > 0 {
+        result.push('\n');
+    }
+    for declaration in &added {
+        result.push_str(&format!("{}\n", declaration));
+    }
+    if offset == 0 {
+        result.push('\n');
+    }
+    result.push_str(&content[offset..]);
+
+    (result, added)
 }
 
-/// Removes the `includes` at the given lines from the `file`.
-pub fn remove_includes(file: &Path, includes: &[usize]) -> io::Result<()> {
-    let temppath = file.with_extension(".tmp");
-    {
-        let original = BufReader::new(File::open(file)?);
-        let mut tempfile = BufWriter::new(File::create(&temppath)?);
-
-        // line numbers starting with 1
-        let lines_to_remove = includes.iter().map(|i| i - 1).collect::<HashSet<_>>();
-
-        for (i, line) in original.split(b'\n').enumerate() {
-            let line = line?;
-            if !lines_to_remove.contains(&i) {
-                tempfile.write_all(&line)?;
-                tempfile.write_all(b"\n")?;
+/// Returns whether the line contains a computed include (`#include MACRO`)
+pub fn is_computed_include(line: &str) -> bool {
+    RE_INCLUDE
+        .captures(line)
+        .is_some_and(|captures| captures.get(3).is_some())
+}
+
+/// Returns the names of the macros that are tested in `#if`, `#ifdef`, `#elif`, ...
+/// of the file `content`, with the line of their first condition (starting with 1).
+pub fn conditional_macros(content: &str) -> HashMap<String, usize> {
+    let mut result = HashMap::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        // Conditions might be continued on the next lines
+        let mut line = line.to_string();
+        while line.ends_with('\\') {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next),
+                None => break,
             }
         }
-    };
+        if let Some(captures) = RE_CONDITION.captures(&line) {
+            // Skip comments
+            let condition = captures[1].split("//").next().unwrap_or_default();
+            let condition = condition.split("/*").next().unwrap_or_default();
+            let condition = RE_HAS_INCLUDE.replace_all(condition, "");
+            for name in RE_IDENTIFIER.find_iter(&condition) {
+                if !CONDITION_KEYWORDS.contains(&name.as_str()) {
+                    result.entry(name.as_str().to_string()).or_insert(i + 1);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Removes the `includes` at the given lines from the file `content`.
+pub fn remove_includes(content: &str, includes: &[usize]) -> String {
+    // line numbers starting with 1
+    let lines_to_remove = includes.iter().map(|i| i - 1).collect::<HashSet<_>>();
+
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .filter(|(i, _)| !lines_to_remove.contains(i))
+        .map(|(_, line)| line)
+        .collect()
+}
 
-    fs::remove_file(file)?;
-    fs::rename(&temppath, file)?;
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    Ok(())
+    #[test]
+    fn test_remove_includes() {
+        let content = "#include \"A.hpp\"\n#include <vector>\n\nint x;";
+        assert_eq!(
+            remove_includes(content, &[2]),
+            "#include \"A.hpp\"\n\nint x;"
+        );
+    }
+
+    #[test]
+    fn test_add_includes() {
+        let content = "#include \"Main.hpp\"\n#include <vector>\n\nint x;\n";
+        let (result, added) = add_includes(
+            content,
+            false,
+            vec![
+                IncludeStatement::Global("vector".into()),
+                IncludeStatement::Local("B.hpp".into()),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(added, vec![IncludeStatement::Local("B.hpp".into())]);
+        assert_eq!(
+            result,
+            "#include \"Main.hpp\"\n#include \"B.hpp\"\n#include <vector>\n\nint x;\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_includes() {
+        let content = "#include \"A.hpp\"\n\
+                       #include_next <limits.h>\n\
+                       #import \"Lib++/My Header.h\"\n\
+                       #include HEADER_NAME\n\
+                       \n\
+                       int x;\n";
+        let (_, includes) = parse_includes_str(content, &RE_INCLUDE, false);
+        let mut includes = includes.into_iter().collect::<Vec<_>>();
+        includes.sort();
+        assert_eq!(includes, ["A.hpp", "Lib++/My Header.h", "limits.h"]);
+
+        let (_, includes) = parse_includes_str(content, &RE_LOCAL_INCLUDE, false);
+        assert_eq!(includes.len(), 2);
+
+        assert!(is_computed_include("#  include HEADER_NAME // comment"));
+        assert!(!is_computed_include("#include <vector>"));
+    }
+
+    #[test]
+    fn test_add_declarations() {
+        let content = "#pragma once\n#include <vector>\n\nclass A;\n";
+        let (result, added) = add_declarations(
+            content,
+            true,
+            vec!["class A;".into(), "namespace b { class B; }".into()].into_iter(),
+        );
+        assert_eq!(added, vec!["namespace b { class B; }".to_string()]);
+        assert_eq!(
+            result,
+            "#pragma once\n#include <vector>\n\nnamespace b { class B; }\n\nclass A;\n"
+        );
+
+        // Inserted after the header guard
+        let content = "#ifndef A_H\n#define A_H\n\nvoid a(B *b);\n\n#endif\n";
+        let (result, _) = add_declarations(content, true, vec!["class B;".into()].into_iter());
+        assert_eq!(
+            result,
+            "#ifndef A_H\n#define A_H\n\nclass B;\n\nvoid a(B *b);\n\n#endif\n"
+        );
+    }
+
+    #[test]
+    fn test_conditional_macros() {
+        let content = "#include \"config.h\"\n\
+                       #ifdef FEATURE_X // FEATURE_Y\n\
+                       #elif defined(FEATURE_Z) && VERSION > 2\n\
+                       #endif\n\
+                       # if __has_include(<vector>)\n\
+                       #endif\n\
+                       int ifdef_x;\n";
+        let mut macros = conditional_macros(content).into_iter().collect::<Vec<_>>();
+        macros.sort();
+        assert_eq!(
+            macros,
+            [
+                ("FEATURE_X".to_string(), 2),
+                ("FEATURE_Z".to_string(), 3),
+                ("VERSION".to_string(), 3)
+            ]
+        );
+
+        let content = "#if defined(FEATURE_X) && \\\n    defined(FEATURE_Y) \\\r\n    || LEGACY\n\
+                       #endif\n";
+        let mut macros = conditional_macros(content).into_keys().collect::<Vec<_>>();
+        macros.sort();
+        assert_eq!(macros, ["FEATURE_X", "FEATURE_Y", "LEGACY"]);
+    }
 }
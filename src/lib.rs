@@ -0,0 +1,47 @@
+//! A clang tool for finding and removing unused C/C++ includes.
+//!
+//! ```no_run
+//! use unused_includes_rs::{Edits, Project};
+//!
+//! let project = Project::builder()
+//!     .compilations("build/compile_commands.json")
+//!     .build()
+//!     .expect("Error parsing compilation database");
+//!
+//! // Analyze a single file
+//! let analysis = project.analyze("src/Main.cpp".as_ref()).unwrap();
+//! println!("unused: {:?}", analysis.unused);
+//!
+//! // Plan the removal in the whole project and apply it afterwards
+//! let (mut edits, _report) = project.remove_unused_includes(&project.files(), Edits::new(true), 4);
+//! edits.apply().unwrap();
+//! ```
+
+#[macro_use]
+extern crate log;
+
+pub mod analyze;
+pub mod compilations;
+pub mod config;
+pub mod dependencies;
+pub mod edits;
+pub mod error;
+pub mod fileio;
+pub mod mappings;
+pub mod report;
+pub mod util;
+
+mod clangfmt;
+mod project;
+mod stdlib;
+mod walk;
+
+pub use analyze::{Analysis, Explanation, HeaderCheck, Include};
+pub use compilations::Compilations;
+pub use config::{Association, Config, ConfigFile, Settings};
+pub use dependencies::Dependencies;
+pub use edits::Edits;
+pub use error::{Error, Result};
+pub use mappings::Mappings;
+pub use project::{Builder, Project};
+pub use report::{Format, Report};
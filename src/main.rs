@@ -1,39 +1,142 @@
-use std::collections::HashSet;
 use std::env::args;
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::vec::Vec;
 
+use log::{error, info, Level, LevelFilter, Log, Metadata, Record};
+use structopt::clap;
 use structopt::StructOpt;
 
-mod analyze;
-mod compilations;
-use compilations::Compilations;
-mod clangfmt;
-mod dependencies;
-use dependencies::Dependencies;
-mod fileio;
-mod util;
+use unused_includes_rs::{ConfigFile, Dependencies, Edits, Error, Format, Project, Result};
 
+/// Finds and removes unused C/C++ includes.
+///
+/// Exits with 1 if `--dry-run` found changes and with 2 if any file could not be analyzed or modified.
 #[derive(StructOpt)]
+#[structopt(setting = structopt::clap::AppSettings::SubcommandsNegateReqs)]
 struct ToolArgs {
-    #[structopt(parse(from_os_str))]
-    file: PathBuf,
-    #[structopt(short, long, default_value = ".")]
-    filter: regex::Regex,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+    #[structopt(parse(from_os_str), required_unless = "all")]
+    file: Option<PathBuf>,
+    /// Analyze all sources of the compilation database and all indexed headers.
+    #[structopt(long, conflicts_with = "file", requires = "comp")]
+    all: bool,
+    /// Only sources matching this regex are indexed and analyzed [default: .]
+    #[structopt(short, long)]
+    filter: Option<regex::Regex>,
     #[structopt(short, long = "compilations", parse(from_os_str))]
     comp: Option<PathBuf>,
     #[structopt(long, parse(from_os_str))]
     index: Option<PathBuf>,
-    #[structopt(long, default_value = "clang-format")]
-    clang_format: String,
-    #[structopt(long, default_value = "(/private/|[_/]impl[_\\./])")]
-    ignore_includes: regex::Regex,
+    /// The clang-format executable [default: clang-format]
+    #[structopt(long)]
+    clang_format: Option<String>,
+    /// Includes matching this regex are never removed [default: (/private/|[_/]impl[_\\./])]
+    #[structopt(long)]
+    ignore_includes: Option<regex::Regex>,
+    /// IWYU (`.imp`) or TOML files mapping private to public headers.
+    #[structopt(long = "mapping-file", parse(from_os_str))]
+    mapping_files: Vec<PathBuf>,
+    /// Only report the planned edits without modifying any file.
+    /// Exits with a non-zero code if any file would be changed.
+    #[structopt(long, alias = "check")]
+    dry_run: bool,
+    /// Print all planned edits as unified diff instead of modifying the files.
+    #[structopt(long)]
+    diff: bool,
+    /// Write all planned edits as unified diff to the given file instead of modifying the files.
+    #[structopt(long, parse(from_os_str))]
+    patch: Option<PathBuf>,
+    /// Additional build configuration, like `--variant=-DENABLE_FOO`.
+    /// Only includes that are unused in all configurations are removed.
+    #[structopt(long = "variant", allow_hyphen_values = true)]
+    variants: Vec<String>,
+    /// Directly include used headers that are only included indirectly.
+    #[structopt(long)]
+    add_missing: bool,
+    /// Replace includes that are only used through pointers or references
+    /// with forward declarations of the used classes.
+    #[structopt(long)]
+    forward_declare: bool,
+    /// Number of files that are analyzed concurrently.
+    #[structopt(short, long, default_value = "1")]
+    jobs: usize,
+    /// Output format: text, json, sarif or compiler (`file:line:col: warning: message`).
+    /// Except for text, the findings are printed to stdout at the end,
+    /// progress messages are redirected to stderr.
+    #[structopt(long, default_value = "text")]
+    format: Format,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Explains which symbols of the file require the include and through which includes they are reached.
+    Why {
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// Name of the include, like it is written in the file
+        include: String,
+    },
+    /// Checks that the headers compile on their own and reports the missing includes.
+    ///
+    /// Without files, all indexed headers are checked.
+    /// Exits with 1 if any header is not self-contained.
+    /// Supports the text, json and compiler formats.
+    CheckHeaders {
+        #[structopt(parse(from_os_str))]
+        files: Vec<PathBuf>,
+    },
+}
+
+/// Prints the log messages to stdout, or to stderr if stdout is reserved for a report
+struct Logger {
+    stderr: AtomicBool,
+}
+
+static LOGGER: Logger = Logger {
+    stderr: AtomicBool::new(false),
+};
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Error => eprintln!("Error: {}", record.args()),
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ if self.stderr.load(Ordering::Relaxed) => eprintln!("{}", record.args()),
+            _ => println!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
 }
 
 fn main() {
-    println!("libclang: {}", clang::get_version());
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
 
+    match run() {
+        Ok(code) => process::exit(code),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(2);
+        }
+    }
+}
+
+/// Returns the exit code
+fn run() -> Result<i32> {
     // Split command line args at '--'
     let (tool_args, ci_args) = {
         let mut args = args().collect::<Vec<_>>();
@@ -47,111 +150,212 @@ fn main() {
     };
 
     let ToolArgs {
+        command,
         file,
+        all,
         filter,
         comp,
         index,
         clang_format,
         ignore_includes,
+        mapping_files,
+        dry_run,
+        diff,
+        patch,
+        variants,
+        add_missing,
+        forward_declare,
+        jobs,
+        format,
     } = ToolArgs::from_iter(tool_args.iter());
 
-    if let Ok(mut val) = clangfmt::EXEC.write() {
-        *val = clang_format;
+    if matches!(command, Some(Command::CheckHeaders { .. })) && format == Format::Sarif {
+        clap::Error::with_description(
+            "The check-headers command supports the text, json and compiler formats",
+            clap::ErrorKind::InvalidValue,
+        )
+        .exit();
     }
 
-    let file = file.canonicalize().unwrap();
+    // Keep stdout clean for the report or diff
+    if format != Format::Text || diff {
+        LOGGER.stderr.store(true, Ordering::Relaxed);
+    }
+
+    info!("libclang: {}", clang::get_version());
+
+    // Command line args overwrite the config files
+    let mut builder = Project::builder()
+        .config(ConfigFile {
+            filter: filter.map(|r| r.as_str().into()),
+            ignore_includes: ignore_includes.map(|r| r.as_str().into()),
+            clang_format,
+            mapping_files: if mapping_files.is_empty() {
+                None
+            } else {
+                Some(mapping_files)
+            },
+            ..ConfigFile::default()
+        })
+        .args(ci_args)
+        .variants(
+            variants
+                .iter()
+                .map(|v| shlex::split(v).unwrap_or_default())
+                .collect(),
+        )
+        .add_missing(add_missing)
+        .forward_declare(forward_declare);
+
+    let backup_index = comp.is_some() && index.is_none();
+    if let Some(comp) = comp {
+        builder = builder.compilations(comp);
+        if let Some(index) = index {
+            info!("Loading dependency tree...");
+            let file =
+                File::open(&index).map_err(|e| Error::Index(index.clone(), e.to_string()))?;
+            let index: Dependencies = serde_json::from_reader(file)
+                .map_err(|e| Error::Index(index.clone(), e.to_string()))?;
+            builder = builder.index(index);
+        }
+    } else {
+        info!("No compilation database provided. Analyzing only the given source.");
+    }
 
-    let (include_paths, index, ci_args) = if let Some(comp) = comp {
-        println!("Parsing compilaton database...");
-        let compilations =
-            Compilations::parse(&comp, &filter).expect("Error parsing compilation database");
+    let project = builder.build()?;
 
-        let include_paths = compilations.collect_include_paths();
-        println!("Include paths: {:?}", include_paths);
+    if backup_index {
+        let path = PathBuf::from("dependencies.json");
+        let file = File::create(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        serde_json::to_writer(file, project.index())
+            .map_err(|e| Error::Io(path.clone(), e.into()))?;
+    }
 
-        let index = if let Some(index) = index {
-            println!("Loading dependency tree...");
-            let file = File::open(index).expect("Error opening include index");
-            serde_json::from_reader(file).expect("Error parsing include index")
+    if let Some(Command::Why { file, include }) = command {
+        let file = file.canonicalize().map_err(|e| Error::Io(file, e))?;
+        let explanation = project.why(&file, &include)?;
+        let mut out = io::stdout();
+        if format == Format::Json {
+            serde_json::to_writer_pretty(&mut out, &explanation)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(out))
         } else {
-            println!("Creating dependency tree...");
-            let index = Dependencies::create(&compilations.sources(), &include_paths, &filter);
-            let file = File::create("dependencies.json").expect("Could not backup index");
-            serde_json::to_writer(file, &index).expect("Could not backup index");
-            index
-        };
-
-        index.print(&file);
-
-        let mut new_ci_args = compilations
-            .get_related_args(&file, &index)
-            .expect("Missing compiler args in compilation database");
-        // add custom args
-        new_ci_args.extend(ci_args);
-
-        (include_paths, index, new_ci_args)
+            write!(out, "{}", explanation)
+        }
+        .map_err(stdout_error)?;
+        return Ok(0);
+    }
+    if let Some(Command::CheckHeaders { files }) = command {
+        return check_headers(&project, files, format);
+    }
+
+    let files = if all {
+        // Headers are visited before the files including them
+        project.files()
     } else {
-        println!("No compilation database provided. Analyzing only the given source.");
-        let include_paths = util::include_paths(&ci_args.join(" "))
-            .map(PathBuf::from)
-            .collect::<Vec<_>>();
-        (include_paths, Dependencies::new(), ci_args)
+        let file = file.unwrap();
+        let file = file.canonicalize().map_err(|e| Error::Io(file, e))?;
+        project.index().print(&file);
+        vec![file]
     };
 
-    println!("Analyzing {}", file.to_string_lossy());
-    let mut visited = HashSet::new();
-
-    remove_unused_includes(
-        &file,
-        &ci_args,
-        &ignore_includes,
-        &include_paths,
-        &index,
-        &mut visited,
+    let (edits, report) = project.remove_unused_includes(
+        &files,
+        Edits::new(dry_run || diff || patch.is_some()),
+        jobs,
     );
-}
 
-fn remove_unused_includes(
-    file: &Path,
-    args: &[String],
-    ignore_includes: &regex::Regex,
-    include_paths: &[PathBuf],
-    index: &Dependencies,
-    visited: &mut HashSet<PathBuf>,
-) {
-    if !visited.insert(PathBuf::from(file)) {
-        println!(" -> Circular includes: {}", file.to_string_lossy());
-    } else if let Ok(includes) = analyze::unused_includes(file, args, ignore_includes) {
-        println!(" -> Remove {:?}", includes);
-
-        if !includes.is_empty() {
-            let lines = includes.iter().map(|i| i.line).collect::<Vec<_>>();
-            fileio::remove_includes(file, &lines).expect("Could not remove includes");
-            // Sort includes
-            clangfmt::includes(file).expect("Clang-format failed");
+    match format {
+        Format::Text => Ok(()),
+        Format::Json => report.json(&mut io::stdout()),
+        Format::Sarif => report.sarif(&mut io::stdout()),
+        Format::Compiler => report.compiler(&mut io::stdout()),
+    }
+    .map_err(stdout_error)?;
+
+    if diff {
+        edits.diff(&mut io::stdout()).map_err(stdout_error)?;
+    }
+    if let Some(patch) = patch {
+        File::create(&patch)
+            .and_then(|mut file| edits.diff(&mut file))
+            .map_err(|e| Error::Io(patch, e))?;
+    }
+
+    if dry_run {
+        edits.print();
+    }
+
+    if !report.errors.is_empty() {
+        eprintln!("{} failures:", report.errors.len());
+        for err in &report.errors {
+            eprintln!("  {}", err);
         }
+        Ok(2)
+    } else if dry_run && !edits.is_empty() {
+        Ok(1)
+    } else {
+        Ok(0)
+    }
+}
 
-        for dependency in index.get(file) {
-            println!("Analyzing {}", dependency.to_string_lossy());
-            // Add removed includes
-            if !includes.is_empty() {
-                let includes = includes.iter().map(|i| {
-                    i.get_local(dependency, include_paths).map_or_else(
-                        || fileio::IncludeStatement::Global(i.name.clone()),
-                        fileio::IncludeStatement::Local,
-                    )
-                });
-                fileio::add_includes(dependency, includes).expect("Could not propagate includes");
+/// Checks the headers and returns the exit code
+fn check_headers(project: &Project, files: Vec<PathBuf>, format: Format) -> Result<i32> {
+    let files = if files.is_empty() {
+        project
+            .files()
+            .into_iter()
+            .filter(|f| project.config().settings(f).is_header_file(f))
+            .collect()
+    } else {
+        files
+            .into_iter()
+            .map(|f| f.canonicalize().map_err(|e| Error::Io(f, e)))
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut checks = Vec::new();
+    let mut errors = Vec::new();
+    for file in &files {
+        info!("Checking {}", file.to_string_lossy());
+        match project.check_header(file) {
+            Ok(check) => {
+                match format {
+                    Format::Text => write!(io::stdout(), "{}", check),
+                    Format::Compiler => check.compiler(&mut io::stdout()),
+                    _ => Ok(()),
+                }
+                .map_err(stdout_error)?;
+                checks.push(check);
+            }
+            Err(err) => {
+                error!("{}", err);
+                errors.push(err);
             }
+        }
+    }
+    if format == Format::Json {
+        let mut out = io::stdout();
+        serde_json::to_writer_pretty(&mut out, &checks)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(out))
+            .map_err(stdout_error)?;
+    }
 
-            remove_unused_includes(
-                dependency,
-                args,
-                ignore_includes,
-                include_paths,
-                index,
-                visited,
-            );
+    if !errors.is_empty() {
+        eprintln!("{} failures:", errors.len());
+        for err in &errors {
+            eprintln!("  {}", err);
         }
+        Ok(2)
+    } else if checks.iter().any(|c| !c.errors.is_empty()) {
+        Ok(1)
+    } else {
+        Ok(0)
     }
 }
+
+/// Error writing the results to stdout
+fn stdout_error(err: io::Error) -> Error {
+    Error::Io(PathBuf::from("<stdout>"), err)
+}
@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::fileio::IncludeStatement;
+use super::stdlib;
+
+lazy_static::lazy_static! {
+    static ref RE_IMP_COMMENT: Regex = Regex::new("(?m)^[ \\t]*#.*$").unwrap();
+    static ref RE_IMP_KEY: Regex = Regex::new("([\\{,][ \\t\\r\\n]*)(\\w+)[ \\t]*:").unwrap();
+    static ref RE_IMP_TRAILING_COMMA: Regex = Regex::new(",([ \\t\\r\\n]*[\\]\\}])").unwrap();
+}
+
+/// Simple mapping file format: `"<private.h>" = "<public.h>"`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MappingFile {
+    include: toml::value::Table,
+}
+
+/// Maps private headers to the public headers that provide their symbols.
+///
+/// They are loaded from IWYU mapping files (`.imp`) or simple TOML files:
+///
+/// ```toml
+/// [include]
+/// "<bits/stl_vector.h>" = "<vector>"
+/// ```
+///
+/// IWYU symbol mappings and regex entries are not supported.
+#[derive(Debug, Default)]
+pub struct Mappings {
+    /// Private include paths and the public includes
+    includes: Vec<(String, IncludeStatement)>,
+}
+
+impl Mappings {
+    /// Loads the mapping file, depending on its extension (`.imp` or `.toml`)
+    pub fn load(path: &Path) -> Result<Mappings, String> {
+        Mappings::load_once(path, &mut HashSet::new())
+    }
+
+    /// Loads the mapping file unless it is contained in the `loaded` files,
+    /// which breaks cyclic references.
+    fn load_once(path: &Path, loaded: &mut HashSet<PathBuf>) -> Result<Mappings, String> {
+        let canonical = path.canonicalize().map_err(|e| e.to_string())?;
+        if !loaded.insert(canonical) {
+            return Ok(Mappings::default());
+        }
+
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut result = Mappings::default();
+
+        if path.extension().is_some_and(|e| e == "toml") {
+            let file: MappingFile = toml::from_str(&content).map_err(|e| e.to_string())?;
+            for (private, public) in file.include {
+                let public = public.as_str().ok_or("Public header is not a string")?;
+                result.insert(&private, public);
+            }
+        } else {
+            let content = RE_IMP_COMMENT.replace_all(&content, "");
+            let content = RE_IMP_KEY.replace_all(&content, "$1\"$2\":");
+            let content = RE_IMP_TRAILING_COMMA.replace_all(&content, "$1");
+            let entries: Vec<Value> = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+
+            for entry in entries {
+                if let Some([private, _, public, _]) =
+                    entry["include"].as_array().map(Vec::as_slice)
+                {
+                    if let (Some(private), Some(public)) = (private.as_str(), public.as_str()) {
+                        if !private.starts_with('@') {
+                            result.insert(private, public);
+                        }
+                    }
+                } else if let Some(reference) = entry["ref"].as_str() {
+                    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                    result.extend(Mappings::load_once(&dir.join(reference), loaded)?);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Built-in mappings of the internal headers of libstdc++ and libc++
+    pub fn stdlib() -> Mappings {
+        Mappings {
+            includes: stdlib::HEADERS
+                .iter()
+                .map(|&(private, public)| (private.into(), IncludeStatement::Global(public.into())))
+                .collect(),
+        }
+    }
+
+    fn insert(&mut self, private: &str, public: &str) {
+        let public = if let Some(local) = public.strip_prefix('"') {
+            IncludeStatement::Local(local.trim_end_matches('"').into())
+        } else {
+            IncludeStatement::Global(public.trim_matches(|c| c == '<' || c == '>').into())
+        };
+        let private = private.trim_matches(|c| c == '<' || c == '>' || c == '"');
+        self.includes.push((private.into(), public));
+    }
+
+    pub fn extend(&mut self, other: Mappings) {
+        self.includes.extend(other.includes);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.includes.is_empty()
+    }
+
+    /// Returns the public include for the header at the given path
+    pub fn public(&self, path: &Path) -> Option<&IncludeStatement> {
+        self.includes
+            .iter()
+            .find(|(private, _)| path.ends_with(private))
+            .map(|(_, public)| public)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env::current_dir;
+
+    #[test]
+    fn test_mappings() {
+        let imp = current_dir().unwrap().join("tests/mappings/std.imp");
+        let mappings = Mappings::load(&imp).unwrap();
+        assert_eq!(
+            mappings.public(Path::new("/usr/include/c++/11/bits/stl_vector.h")),
+            Some(&IncludeStatement::Global("vector".into()))
+        );
+        assert_eq!(
+            mappings.public(Path::new("/src/detail/Impl.hpp")),
+            Some(&IncludeStatement::Local("Api.hpp".into()))
+        );
+        assert_eq!(mappings.public(Path::new("/src/Impl.hpp")), None);
+
+        // Cyclic references are loaded once
+        let imp = current_dir().unwrap().join("tests/mappings/cycle.imp");
+        let mappings = Mappings::load(&imp).unwrap();
+        assert_eq!(mappings.includes.len(), 2);
+        assert_eq!(
+            mappings.public(Path::new("/src/detail/Second.hpp")),
+            Some(&IncludeStatement::Local("Second.hpp".into()))
+        );
+
+        let stdlib = Mappings::stdlib();
+        assert_eq!(
+            stdlib.public(Path::new("/usr/include/c++/11/bits/basic_string.h")),
+            Some(&IncludeStatement::Global("string".into()))
+        );
+        assert_eq!(stdlib.public(Path::new("/usr/include/c++/11/string")), None);
+    }
+}
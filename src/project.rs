@@ -0,0 +1,278 @@
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use super::analyze::{self, Analysis, Explanation, HeaderCheck};
+use super::compilations::Compilations;
+use super::config::{Config, ConfigFile};
+use super::dependencies::Dependencies;
+use super::edits::Edits;
+use super::error::{Error, Result};
+use super::report::Report;
+use super::util;
+use super::walk::Walk;
+
+/// Builder for a [`Project`]
+#[derive(Default)]
+pub struct Builder {
+    compilations: Option<PathBuf>,
+    index: Option<Dependencies>,
+    overrides: ConfigFile,
+    args: Vec<String>,
+    variants: Vec<Vec<String>>,
+    add_missing: bool,
+    forward_declare: bool,
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// Path to the clang compilation database (`compile_commands.json`).
+    ///
+    /// Without it, only the given files are analyzed using the additional args.
+    pub fn compilations<P: Into<PathBuf>>(mut self, path: P) -> Builder {
+        self.compilations = Some(path.into());
+        self
+    }
+
+    /// Use an existing dependency index instead of creating a new one.
+    pub fn index(mut self, index: Dependencies) -> Builder {
+        self.index = Some(index);
+        self
+    }
+
+    /// Settings that overwrite those of the config files.
+    pub fn config(mut self, overrides: ConfigFile) -> Builder {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Additional compiler args.
+    pub fn args(mut self, args: Vec<String>) -> Builder {
+        self.args = args;
+        self
+    }
+
+    /// Additional build configurations, like `-DENABLE_FOO`.
+    ///
+    /// Each variant is added to every compile command of a file.
+    /// Only includes that are unused in all configurations are removed.
+    pub fn variants(mut self, variants: Vec<Vec<String>>) -> Builder {
+        self.variants = variants;
+        self
+    }
+
+    /// Add includes for used headers that are only included indirectly.
+    pub fn add_missing(mut self, add_missing: bool) -> Builder {
+        self.add_missing = add_missing;
+        self
+    }
+
+    /// Replace includes that are only used for incomplete types with forward declarations.
+    pub fn forward_declare(mut self, forward_declare: bool) -> Builder {
+        self.forward_declare = forward_declare;
+        self
+    }
+
+    /// Parses the compilation database and creates the dependency index if necessary.
+    pub fn build(self) -> Result<Project> {
+        let config = Config::new(self.overrides);
+
+        if let Some(comp) = self.compilations {
+            info!("Parsing compilaton database...");
+            let compilations = Compilations::parse(&comp, |file| config.is_selected(file))?;
+
+            let include_paths = compilations.collect_include_paths();
+            info!("Include paths: {:?}", include_paths);
+
+            let index = self.index.unwrap_or_else(|| {
+                info!("Creating dependency tree...");
+                Dependencies::create(&compilations.sources(), &include_paths, &config)
+            });
+
+            Ok(Project {
+                config,
+                compilations: Some(compilations),
+                include_paths,
+                index,
+                args: self.args,
+                variants: self.variants,
+                add_missing: self.add_missing,
+                forward_declare: self.forward_declare,
+            })
+        } else {
+            let include_paths = util::include_paths(&self.args.join(" "))
+                .map(PathBuf::from)
+                .collect::<Vec<_>>();
+
+            Ok(Project {
+                config,
+                compilations: None,
+                include_paths,
+                index: self.index.unwrap_or_default(),
+                args: self.args,
+                variants: self.variants,
+                add_missing: self.add_missing,
+                forward_declare: self.forward_declare,
+            })
+        }
+    }
+}
+
+/// The sources and headers of a project, that are analyzed together.
+pub struct Project {
+    config: Config,
+    compilations: Option<Compilations>,
+    include_paths: Vec<PathBuf>,
+    index: Dependencies,
+    args: Vec<String>,
+    variants: Vec<Vec<String>>,
+    add_missing: bool,
+    forward_declare: bool,
+}
+
+impl Project {
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn include_paths(&self) -> &[PathBuf] {
+        &self.include_paths
+    }
+
+    /// Index of the files and the files including them
+    pub fn index(&self) -> &Dependencies {
+        &self.index
+    }
+
+    /// Returns all sources and indexed headers matching the filter of their directory.
+    ///
+    /// Headers precede the files including them.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let sources = self
+            .compilations
+            .as_ref()
+            .map(Compilations::sources)
+            .unwrap_or_default();
+        self.index
+            .ordered(&sources)
+            .into_iter()
+            .filter(|f| self.config.is_selected(f))
+            .collect()
+    }
+
+    /// Returns the compiler args for the given file
+    pub fn args(&self, file: &Path) -> Option<Vec<String>> {
+        self.configurations(file)?.into_iter().next()
+    }
+
+    /// Returns the compiler args of all build configurations of the given file.
+    ///
+    /// These are the compile commands of the file combined with the variants.
+    pub fn configurations(&self, file: &Path) -> Option<Vec<Vec<String>>> {
+        let commands = if let Some(compilations) = &self.compilations {
+            compilations.get_related_configurations(file, &self.index)
+        } else {
+            vec![Vec::new()]
+        };
+        if commands.is_empty() {
+            return None;
+        }
+
+        let mut configurations = Vec::new();
+        for args in &commands {
+            for variant in iter::once(&Vec::new()).chain(&self.variants) {
+                // add custom args
+                configurations.push(
+                    args.iter()
+                        .chain(variant)
+                        .chain(&self.args)
+                        .cloned()
+                        .collect(),
+                );
+            }
+        }
+        Some(configurations)
+    }
+
+    /// Analyzes a single file in all build configurations without modifying it.
+    pub fn analyze(&self, file: &Path) -> Result<Analysis> {
+        let configurations = self
+            .configurations(file)
+            .ok_or_else(|| Error::MissingArgs(file.into()))?;
+        analyze::analyze_configurations(file, &configurations, &self.config.settings(file), &[])
+    }
+
+    /// Explains which symbols of the `file` require the `include`.
+    pub fn why(&self, file: &Path, include: &str) -> Result<Explanation> {
+        let args = self
+            .args(file)
+            .ok_or_else(|| Error::MissingArgs(file.into()))?;
+        analyze::why(file, &args, &self.config.settings(file), &[], include)
+    }
+
+    /// Checks whether the header compiles on its own.
+    ///
+    /// Errors are matched with the missing includes, that are found with the first
+    /// sourcefile including the header.
+    pub fn check_header(&self, file: &Path) -> Result<HeaderCheck> {
+        let args = self
+            .args(file)
+            .ok_or_else(|| Error::MissingArgs(file.into()))?;
+        let context = self
+            .compilations
+            .as_ref()
+            .and_then(|c| c.get_related_source(file, &self.index))
+            .filter(|source| *source != file)
+            .and_then(|source| Some((source, self.args(source)?)));
+        analyze::check_header(
+            file,
+            &args,
+            context
+                .as_ref()
+                .map(|(source, args)| (*source, args.as_slice())),
+            &self.config.settings(file),
+            &[],
+        )
+    }
+
+    /// Removes the unused includes from the `files` and all files including them.
+    ///
+    /// The removed includes are added to the including files.
+    /// Unless `edits` is in dry-run mode, the files are modified directly.
+    pub fn remove_unused_includes(
+        &self,
+        files: &[PathBuf],
+        edits: Edits,
+        jobs: usize,
+    ) -> (Edits, Report) {
+        let mut roots = Vec::with_capacity(files.len());
+        let mut errors = Vec::new();
+        for file in files {
+            if let Some(configurations) = self.configurations(file) {
+                roots.push((file.clone(), configurations));
+            } else {
+                let err = Error::MissingArgs(file.clone());
+                error!("{}", err);
+                errors.push(err);
+            }
+        }
+
+        let walk = Walk {
+            config: &self.config,
+            include_paths: &self.include_paths,
+            index: &self.index,
+            add_missing: self.add_missing,
+            forward_declare: self.forward_declare,
+        };
+        let (edits, mut report) = walk.run(&roots, edits, jobs);
+        errors.append(&mut report.errors);
+        report.errors = errors;
+        (edits, report)
+    }
+}
@@ -0,0 +1,406 @@
+use std::env::current_dir;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::analyze::{Diagnostic, ForwardDeclarable, IgnoredInclude, Include, MissingInclude};
+use super::error::Error;
+
+/// Output format of the results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable progress messages
+    Text,
+    /// Machine-readable report printed at the end
+    Json,
+    /// SARIF 2.1.0 log printed at the end
+    Sarif,
+    /// Compiler-style `file:line:col: warning: message` lines printed at the end
+    Compiler,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "sarif" => Ok(Format::Sarif),
+            "compiler" => Ok(Format::Compiler),
+            _ => Err(format!("Unknown format '{}'", s)),
+        }
+    }
+}
+
+/// Include that has been added to a dependency
+#[derive(Debug, Clone, Serialize)]
+pub struct Propagation {
+    pub dependency: PathBuf,
+    pub include: String,
+}
+
+/// Findings for a single analyzed file
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub file: PathBuf,
+    pub unused: Vec<Include>,
+    pub ignored: Vec<IgnoredInclude>,
+    pub missing: Vec<MissingInclude>,
+    pub forward: Vec<ForwardDeclarable>,
+    pub associated_not_first: Option<Include>,
+    pub propagated: Vec<Propagation>,
+    /// Includes propagated into this file by previous edits that are unused and removed again,
+    /// located where they were added
+    pub reverted: Vec<Include>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Findings for all analyzed files
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub files: Vec<FileReport>,
+    /// Files that could not be analyzed or modified
+    pub errors: Vec<Error>,
+}
+
+/// SARIF rules: (id, description)
+const SARIF_RULES: [(&str, &str); 8] = [
+    ("unused-include", "Include is not used by the file"),
+    (
+        "include-propagated",
+        "Include removed from a header is added to the files including it",
+    ),
+    (
+        "analysis-skipped",
+        "File could not be analyzed due to parse errors",
+    ),
+    ("edit-failed", "File could not be modified"),
+    ("missing-include", "Used header is only included indirectly"),
+    (
+        "forward-declaration",
+        "Include can be replaced by forward declarations",
+    ),
+    (
+        "associated-header-first",
+        "Associated header should be the first include",
+    ),
+    (
+        "propagation-reverted",
+        "Include propagated from a header is unused and removed again",
+    ),
+];
+
+impl Report {
+    /// Writes the report in the json format
+    pub fn json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, self)?;
+        writeln!(out)
+    }
+
+    /// Writes the findings as compiler-style diagnostics, which are understood by editors.
+    ///
+    /// The clang diagnostics keep their native location format.
+    pub fn compiler<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for file in &self.files {
+            for diagnostic in &file.diagnostics {
+                writeln!(out, "{}", diagnostic)?;
+            }
+            for include in &file.unused {
+                writeln!(
+                    out,
+                    "{}:{}:{}: warning: unused include '{}'",
+                    file.file.to_string_lossy(),
+                    include.line,
+                    include.column,
+                    include.name
+                )?;
+            }
+            for missing in &file.missing {
+                writeln!(
+                    out,
+                    "{}:{}:{}: warning: missing include '{}' for {}",
+                    file.file.to_string_lossy(),
+                    missing.include.line,
+                    missing.include.column,
+                    missing.include.name,
+                    missing.symbols.join(", ")
+                )?;
+            }
+            for forward in &file.forward {
+                writeln!(
+                    out,
+                    "{}:{}:{}: note: include '{}' can be replaced by '{}'",
+                    file.file.to_string_lossy(),
+                    forward.include.line,
+                    forward.include.column,
+                    forward.include.name,
+                    forward.declarations.join(" ")
+                )?;
+            }
+            for include in &file.reverted {
+                writeln!(
+                    out,
+                    "{}:{}:{}: note: propagated include '{}' is unused and not added",
+                    file.file.to_string_lossy(),
+                    include.line,
+                    include.column,
+                    include.name
+                )?;
+            }
+            if let Some(include) = &file.associated_not_first {
+                writeln!(
+                    out,
+                    "{}:{}:{}: warning: associated header '{}' should be the first include",
+                    file.file.to_string_lossy(),
+                    include.line,
+                    include.column,
+                    include.name
+                )?;
+            }
+        }
+        for error in &self.errors {
+            writeln!(
+                out,
+                "{}: error: {}",
+                error.file().to_string_lossy(),
+                error.message()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the report as SARIF 2.1.0 log
+    pub fn sarif<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let root = current_dir()?;
+        let mut results = Vec::new();
+
+        for file in &self.files {
+            for include in &file.unused {
+                let location =
+                    sarif_location(&root, &file.file, Some((include.line, include.column)));
+                results.push(json!({
+                    "ruleId": SARIF_RULES[0].0,
+                    "ruleIndex": 0,
+                    "level": "warning",
+                    "message": { "text": format!("Unused include '{}'", include.name) },
+                    "locations": [location],
+                    "fixes": [{
+                        "description": { "text": format!("Remove include '{}'", include.name) },
+                        "artifactChanges": [{
+                            "artifactLocation": sarif_artifact(&root, &file.file),
+                            "replacements": [{
+                                "deletedRegion": {
+                                    "startLine": include.line,
+                                    "startColumn": 1,
+                                    "endLine": include.line + 1,
+                                    "endColumn": 1,
+                                }
+                            }]
+                        }]
+                    }],
+                }));
+            }
+            for missing in &file.missing {
+                let position = (missing.include.line, missing.include.column);
+                results.push(json!({
+                    "ruleId": SARIF_RULES[4].0,
+                    "ruleIndex": 4,
+                    "level": "note",
+                    "message": {
+                        "text": format!(
+                            "Include '{}' directly for {}",
+                            missing.include.name,
+                            missing.symbols.join(", ")
+                        )
+                    },
+                    "locations": [sarif_location(&root, &file.file, Some(position))],
+                }));
+            }
+            for forward in &file.forward {
+                let position = (forward.include.line, forward.include.column);
+                results.push(json!({
+                    "ruleId": SARIF_RULES[5].0,
+                    "ruleIndex": 5,
+                    "level": "note",
+                    "message": {
+                        "text": format!(
+                            "Replace include '{}' by '{}'",
+                            forward.include.name,
+                            forward.declarations.join(" ")
+                        )
+                    },
+                    "locations": [sarif_location(&root, &file.file, Some(position))],
+                }));
+            }
+            if let Some(include) = &file.associated_not_first {
+                let position = (include.line, include.column);
+                results.push(json!({
+                    "ruleId": SARIF_RULES[6].0,
+                    "ruleIndex": 6,
+                    "level": "warning",
+                    "message": {
+                        "text": format!(
+                            "Associated header '{}' should be the first include",
+                            include.name
+                        )
+                    },
+                    "locations": [sarif_location(&root, &file.file, Some(position))],
+                }));
+            }
+            for propagation in &file.propagated {
+                results.push(json!({
+                    "ruleId": SARIF_RULES[1].0,
+                    "ruleIndex": 1,
+                    "level": "note",
+                    "message": {
+                        "text": format!(
+                            "'{}' removed from '{}' is added",
+                            propagation.include,
+                            file.file.to_string_lossy()
+                        )
+                    },
+                    "locations": [sarif_location(&root, &propagation.dependency, None)],
+                }));
+            }
+            for include in &file.reverted {
+                let position = (include.line, include.column);
+                results.push(json!({
+                    "ruleId": SARIF_RULES[7].0,
+                    "ruleIndex": 7,
+                    "level": "note",
+                    "message": {
+                        "text": format!(
+                            "Propagated include '{}' is unused and not added",
+                            include.name
+                        )
+                    },
+                    "locations": [sarif_location(&root, &file.file, Some(position))],
+                }));
+            }
+        }
+        for error in &self.errors {
+            let rule = if error.is_skipped() { 2 } else { 3 };
+            results.push(json!({
+                "ruleId": SARIF_RULES[rule].0,
+                "ruleIndex": rule,
+                "level": "error",
+                "message": { "text": error.to_string() },
+                "locations": [sarif_location(&root, error.file(), None)],
+            }));
+        }
+
+        let rules = SARIF_RULES
+            .iter()
+            .map(|(id, description)| json!({ "id": id, "shortDescription": { "text": description } }))
+            .collect::<Vec<_>>();
+
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules,
+                    }
+                },
+                "originalUriBaseIds": {
+                    "SRCROOT": { "uri": format!("file://{}/", uri_encode(&root)) }
+                },
+                "results": results,
+            }]
+        });
+        serde_json::to_writer_pretty(&mut *out, &log)?;
+        writeln!(out)
+    }
+}
+
+/// Artifact location relative to the `root` if possible
+fn sarif_artifact(root: &Path, file: &Path) -> Value {
+    if let Ok(relpath) = file.strip_prefix(root) {
+        json!({ "uri": uri_encode(relpath), "uriBaseId": "SRCROOT" })
+    } else {
+        json!({ "uri": format!("file://{}", uri_encode(file)) })
+    }
+}
+
+fn sarif_location(root: &Path, file: &Path, position: Option<(usize, usize)>) -> Value {
+    let mut location = json!({ "artifactLocation": sarif_artifact(root, file) });
+    if let Some((line, column)) = position {
+        location["region"] = json!({ "startLine": line, "startColumn": column });
+    }
+    json!({ "physicalLocation": location })
+}
+
+/// Percent-encodes the path for the use in URIs
+fn uri_encode(path: &Path) -> String {
+    let mut result = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sarif() {
+        let root = current_dir().unwrap();
+        let report = Report {
+            files: vec![FileReport {
+                file: root.join("src/Main.cpp"),
+                unused: vec![Include::new(
+                    "Unused.hpp".into(),
+                    root.join("src/Unused.hpp"),
+                    3,
+                )],
+                ignored: Vec::new(),
+                missing: Vec::new(),
+                forward: Vec::new(),
+                associated_not_first: None,
+                propagated: Vec::new(),
+                reverted: vec![Include::new(
+                    "Propagated.hpp".into(),
+                    root.join("src/Propagated.hpp"),
+                    2,
+                )],
+                diagnostics: Vec::new(),
+            }],
+            errors: vec![Error::Parse(
+                root.join("src/Broken file.cpp"),
+                "Failure".into(),
+            )],
+        };
+
+        let mut buffer = Vec::new();
+        report.sarif(&mut buffer).unwrap();
+        let log: Value = serde_json::from_slice(&buffer).unwrap();
+
+        let results = &log["runs"][0]["results"];
+        assert_eq!(results[0]["ruleId"], "unused-include");
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/Main.cpp");
+        assert_eq!(location["region"]["startLine"], 3);
+        let region = &results[0]["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(region["deletedRegion"]["endLine"], 4);
+
+        assert_eq!(results[1]["ruleId"], "propagation-reverted");
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["region"]["startLine"], 2);
+
+        assert_eq!(results[2]["ruleId"], "analysis-skipped");
+        let location = &results[2]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/Broken%20file.cpp");
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use clang::{Entity, EntityKind};
+
+/// Standard headers of C and C++ standard library symbols
+const SYMBOLS: &[(&str, &str)] = &[
+    // <algorithm>
+    ("std::all_of", "algorithm"),
+    ("std::any_of", "algorithm"),
+    ("std::binary_search", "algorithm"),
+    ("std::copy", "algorithm"),
+    ("std::copy_if", "algorithm"),
+    ("std::count", "algorithm"),
+    ("std::count_if", "algorithm"),
+    ("std::equal", "algorithm"),
+    ("std::fill", "algorithm"),
+    ("std::find", "algorithm"),
+    ("std::find_if", "algorithm"),
+    ("std::for_each", "algorithm"),
+    ("std::lower_bound", "algorithm"),
+    ("std::max", "algorithm"),
+    ("std::max_element", "algorithm"),
+    ("std::min", "algorithm"),
+    ("std::min_element", "algorithm"),
+    ("std::none_of", "algorithm"),
+    ("std::remove", "algorithm"),
+    ("std::remove_if", "algorithm"),
+    ("std::reverse", "algorithm"),
+    ("std::sort", "algorithm"),
+    ("std::stable_sort", "algorithm"),
+    ("std::swap_ranges", "algorithm"),
+    ("std::transform", "algorithm"),
+    ("std::unique", "algorithm"),
+    ("std::upper_bound", "algorithm"),
+    // <array>, <atomic>, <bitset>, <chrono>
+    ("std::array", "array"),
+    ("std::atomic", "atomic"),
+    ("std::atomic_flag", "atomic"),
+    ("std::memory_order", "atomic"),
+    ("std::bitset", "bitset"),
+    ("std::chrono", "chrono"),
+    // <condition_variable>, <deque>, <exception>
+    ("std::condition_variable", "condition_variable"),
+    ("std::deque", "deque"),
+    ("std::exception", "exception"),
+    ("std::exception_ptr", "exception"),
+    ("std::current_exception", "exception"),
+    ("std::rethrow_exception", "exception"),
+    ("std::terminate", "exception"),
+    // <fstream>
+    ("std::basic_filebuf", "fstream"),
+    ("std::basic_fstream", "fstream"),
+    ("std::basic_ifstream", "fstream"),
+    ("std::basic_ofstream", "fstream"),
+    ("std::fstream", "fstream"),
+    ("std::ifstream", "fstream"),
+    ("std::ofstream", "fstream"),
+    // <functional>
+    ("std::function", "functional"),
+    ("std::bind", "functional"),
+    ("std::hash", "functional"),
+    ("std::less", "functional"),
+    ("std::greater", "functional"),
+    ("std::equal_to", "functional"),
+    ("std::reference_wrapper", "functional"),
+    ("std::ref", "functional"),
+    ("std::cref", "functional"),
+    // <future>, <initializer_list>
+    ("std::future", "future"),
+    ("std::promise", "future"),
+    ("std::async", "future"),
+    ("std::initializer_list", "initializer_list"),
+    // <iomanip>, <ios>, <iosfwd>, <iostream>, <istream>
+    ("std::setw", "iomanip"),
+    ("std::setprecision", "iomanip"),
+    ("std::setfill", "iomanip"),
+    ("std::ios_base", "ios"),
+    ("std::basic_ios", "ios"),
+    ("std::streamsize", "ios"),
+    ("std::cin", "iostream"),
+    ("std::cout", "iostream"),
+    ("std::cerr", "iostream"),
+    ("std::clog", "iostream"),
+    ("std::wcout", "iostream"),
+    ("std::basic_istream", "istream"),
+    ("std::istream", "istream"),
+    ("std::ws", "istream"),
+    // <iterator>
+    ("std::advance", "iterator"),
+    ("std::back_inserter", "iterator"),
+    ("std::begin", "iterator"),
+    ("std::distance", "iterator"),
+    ("std::end", "iterator"),
+    ("std::iterator_traits", "iterator"),
+    ("std::next", "iterator"),
+    ("std::prev", "iterator"),
+    ("std::reverse_iterator", "iterator"),
+    // <limits>, <list>, <map>, <memory>, <mutex>
+    ("std::numeric_limits", "limits"),
+    ("std::list", "list"),
+    ("std::map", "map"),
+    ("std::multimap", "map"),
+    ("std::allocator", "memory"),
+    ("std::enable_shared_from_this", "memory"),
+    ("std::make_shared", "memory"),
+    ("std::make_unique", "memory"),
+    ("std::shared_ptr", "memory"),
+    ("std::unique_ptr", "memory"),
+    ("std::weak_ptr", "memory"),
+    ("std::lock_guard", "mutex"),
+    ("std::mutex", "mutex"),
+    ("std::recursive_mutex", "mutex"),
+    ("std::unique_lock", "mutex"),
+    // <new>, <numeric>, <optional>, <ostream>
+    ("std::bad_alloc", "new"),
+    ("std::nothrow", "new"),
+    ("std::accumulate", "numeric"),
+    ("std::iota", "numeric"),
+    ("std::nullopt", "optional"),
+    ("std::optional", "optional"),
+    ("std::basic_ostream", "ostream"),
+    ("std::endl", "ostream"),
+    ("std::flush", "ostream"),
+    ("std::ostream", "ostream"),
+    // <queue>, <random>, <regex>, <set>, <sstream>, <stack>, <stdexcept>
+    ("std::priority_queue", "queue"),
+    ("std::queue", "queue"),
+    ("std::mt19937", "random"),
+    ("std::random_device", "random"),
+    ("std::uniform_int_distribution", "random"),
+    ("std::uniform_real_distribution", "random"),
+    ("std::regex", "regex"),
+    ("std::regex_match", "regex"),
+    ("std::regex_search", "regex"),
+    ("std::multiset", "set"),
+    ("std::set", "set"),
+    ("std::basic_stringstream", "sstream"),
+    ("std::istringstream", "sstream"),
+    ("std::ostringstream", "sstream"),
+    ("std::stringstream", "sstream"),
+    ("std::stack", "stack"),
+    ("std::invalid_argument", "stdexcept"),
+    ("std::logic_error", "stdexcept"),
+    ("std::out_of_range", "stdexcept"),
+    ("std::runtime_error", "stdexcept"),
+    // <string>, <string_view>
+    ("std::basic_string", "string"),
+    ("std::char_traits", "string"),
+    ("std::getline", "string"),
+    ("std::stod", "string"),
+    ("std::stoi", "string"),
+    ("std::stol", "string"),
+    ("std::string", "string"),
+    ("std::to_string", "string"),
+    ("std::u16string", "string"),
+    ("std::u32string", "string"),
+    ("std::wstring", "string"),
+    ("std::basic_string_view", "string_view"),
+    ("std::string_view", "string_view"),
+    // <thread>, <tuple>, <type_traits>, <typeinfo>
+    ("std::thread", "thread"),
+    ("std::this_thread", "thread"),
+    // `std::get` is overloaded in <tuple>, <utility>, <array> and <variant>
+    ("std::make_tuple", "tuple"),
+    ("std::tie", "tuple"),
+    ("std::tuple", "tuple"),
+    ("std::decay", "type_traits"),
+    ("std::enable_if", "type_traits"),
+    ("std::integral_constant", "type_traits"),
+    ("std::is_same", "type_traits"),
+    ("std::remove_reference", "type_traits"),
+    ("std::type_info", "typeinfo"),
+    // <unordered_map>, <unordered_set>, <utility>
+    ("std::unordered_map", "unordered_map"),
+    ("std::unordered_multimap", "unordered_map"),
+    ("std::unordered_multiset", "unordered_set"),
+    ("std::unordered_set", "unordered_set"),
+    ("std::declval", "utility"),
+    ("std::forward", "utility"),
+    ("std::make_pair", "utility"),
+    ("std::move", "utility"),
+    ("std::pair", "utility"),
+    ("std::swap", "utility"),
+    // <variant>, <vector>
+    ("std::get_if", "variant"),
+    ("std::holds_alternative", "variant"),
+    ("std::variant", "variant"),
+    ("std::visit", "variant"),
+    ("std::vector", "vector"),
+    // <cstddef>, <cstdint>, <cstdio>, <cstdlib>, <cstring>, <cmath>
+    ("std::byte", "cstddef"),
+    ("std::nullptr_t", "cstddef"),
+    ("std::ptrdiff_t", "cstddef"),
+    ("std::size_t", "cstddef"),
+    ("std::int8_t", "cstdint"),
+    ("std::int16_t", "cstdint"),
+    ("std::int32_t", "cstdint"),
+    ("std::int64_t", "cstdint"),
+    ("std::uint8_t", "cstdint"),
+    ("std::uint16_t", "cstdint"),
+    ("std::uint32_t", "cstdint"),
+    ("std::uint64_t", "cstdint"),
+    ("std::FILE", "cstdio"),
+    ("std::fprintf", "cstdio"),
+    ("std::printf", "cstdio"),
+    ("std::snprintf", "cstdio"),
+    ("std::abort", "cstdlib"),
+    ("std::exit", "cstdlib"),
+    ("std::free", "cstdlib"),
+    ("std::malloc", "cstdlib"),
+    ("std::memcmp", "cstring"),
+    ("std::memcpy", "cstring"),
+    ("std::memset", "cstring"),
+    ("std::strlen", "cstring"),
+    ("std::abs", "cmath"),
+    ("std::pow", "cmath"),
+    ("std::sqrt", "cmath"),
+    // C standard library
+    ("FILE", "stdio.h"),
+    ("fclose", "stdio.h"),
+    ("fopen", "stdio.h"),
+    ("fprintf", "stdio.h"),
+    ("printf", "stdio.h"),
+    ("puts", "stdio.h"),
+    ("snprintf", "stdio.h"),
+    ("sprintf", "stdio.h"),
+    ("abort", "stdlib.h"),
+    ("atoi", "stdlib.h"),
+    ("calloc", "stdlib.h"),
+    ("exit", "stdlib.h"),
+    ("free", "stdlib.h"),
+    ("malloc", "stdlib.h"),
+    ("realloc", "stdlib.h"),
+    ("memcmp", "string.h"),
+    ("memcpy", "string.h"),
+    ("memmove", "string.h"),
+    ("memset", "string.h"),
+    ("strcmp", "string.h"),
+    ("strcpy", "string.h"),
+    ("strlen", "string.h"),
+    ("strncmp", "string.h"),
+    ("ptrdiff_t", "stddef.h"),
+    ("size_t", "stddef.h"),
+    ("int8_t", "stdint.h"),
+    ("int16_t", "stdint.h"),
+    ("int32_t", "stdint.h"),
+    ("int64_t", "stdint.h"),
+    ("uint8_t", "stdint.h"),
+    ("uint16_t", "stdint.h"),
+    ("uint32_t", "stdint.h"),
+    ("uint64_t", "stdint.h"),
+    ("uintptr_t", "stdint.h"),
+    ("va_list", "stdarg.h"),
+    ("errno", "errno.h"),
+    ("assert", "assert.h"),
+    ("time_t", "time.h"),
+    ("time", "time.h"),
+];
+
+/// Internal headers of libstdc++ and libc++ and their standard headers
+pub const HEADERS: &[(&str, &str)] = &[
+    ("bits/basic_string.h", "string"),
+    ("bits/char_traits.h", "string"),
+    ("bits/stringfwd.h", "string"),
+    ("bits/stl_vector.h", "vector"),
+    ("bits/stl_bvector.h", "vector"),
+    ("bits/stl_list.h", "list"),
+    ("bits/stl_deque.h", "deque"),
+    ("bits/stl_map.h", "map"),
+    ("bits/stl_multimap.h", "map"),
+    ("bits/stl_set.h", "set"),
+    ("bits/stl_multiset.h", "set"),
+    ("bits/stl_queue.h", "queue"),
+    ("bits/stl_stack.h", "stack"),
+    ("bits/unordered_map.h", "unordered_map"),
+    ("bits/unordered_set.h", "unordered_set"),
+    ("bits/stl_algo.h", "algorithm"),
+    ("bits/stl_pair.h", "utility"),
+    ("bits/move.h", "utility"),
+    ("bits/unique_ptr.h", "memory"),
+    ("bits/shared_ptr.h", "memory"),
+    ("bits/std_function.h", "functional"),
+    ("bits/std_mutex.h", "mutex"),
+    ("bits/std_thread.h", "thread"),
+    ("bits/ios_base.h", "ios"),
+    ("bits/stl_iterator.h", "iterator"),
+    ("bits/stl_numeric.h", "numeric"),
+    ("__memory/shared_ptr.h", "memory"),
+    ("__memory/unique_ptr.h", "memory"),
+    ("__functional/function.h", "functional"),
+    ("__utility/pair.h", "utility"),
+    ("__utility/move.h", "utility"),
+    ("__mutex_base", "mutex"),
+];
+
+lazy_static::lazy_static! {
+    static ref SYMBOL_MAP: HashMap<&'static str, &'static str> = SYMBOLS.iter().copied().collect();
+}
+
+/// Returns the qualified name of the entity, without inline and internal namespaces
+/// (like `std::__cxx11` or `std::__1`).
+fn qualified_name(entity: &Entity) -> Option<String> {
+    let mut names = vec![entity.get_name()?];
+    let mut parent = entity.get_semantic_parent();
+    while let Some(entity) = parent {
+        match entity.get_kind() {
+            EntityKind::TranslationUnit => break,
+            EntityKind::Namespace => {
+                let name = entity.get_name()?;
+                if !entity.is_inline_namespace() && !name.starts_with("__") {
+                    names.push(name);
+                }
+            }
+            _ => names.push(entity.get_name()?),
+        }
+        parent = entity.get_semantic_parent();
+    }
+    names.reverse();
+    Some(names.join("::"))
+}
+
+/// Returns the standard header of a standard library symbol.
+///
+/// Members are looked up by their enclosing class (e.g. `std::vector::push_back`)
+/// and symbols of nested namespaces by their namespace (e.g. `std::chrono`).
+pub fn symbol_header(declaration: &Entity) -> Option<&'static str> {
+    if !declaration
+        .get_location()
+        .is_some_and(|l| l.is_in_system_header())
+    {
+        return None;
+    }
+
+    let mut current = Some(*declaration);
+    while let Some(entity) = current {
+        if entity.get_kind() == EntityKind::TranslationUnit {
+            break;
+        }
+        if let Some(header) = qualified_name(&entity).and_then(|n| SYMBOL_MAP.get(n.as_str())) {
+            return Some(header);
+        }
+        current = entity.get_semantic_parent();
+    }
+    None
+}
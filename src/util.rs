@@ -3,10 +3,21 @@ use std::io;
 use std::path::{Path, PathBuf};
 
 /// Returns whether the given path points to a header file
-pub fn is_header_file(path: &Path) -> bool {
+pub fn is_header_file(path: &Path, extensions: &[String]) -> bool {
     path.is_file()
         && matches!(path.extension(),
-            Some(e) if e == "h" || e == "hpp")
+            Some(e) if extensions.iter().any(|x| e == x.as_str()))
+}
+
+/// Returns the relative paths from the source roots (like `src` or `include`)
+/// containing the directory `dir` to `dir`.
+pub fn source_relpaths<'a>(
+    dir: &'a Path,
+    source_roots: &'a [String],
+) -> impl Iterator<Item = &'a Path> {
+    dir.ancestors()
+        .filter(move |a| source_roots.iter().any(|r| a.ends_with(r)))
+        .filter_map(move |a| dir.strip_prefix(a).ok())
 }
 
 /// Parses the include paths from the given compiler commandline.
@@ -23,8 +34,13 @@ pub fn include_paths(command: &str) -> impl Iterator<Item = &str> {
 
 /// Finds the corresponding filepath to the given `include`.
 ///
-/// It also handles relative includes and 'src/main/...' is correctly resolved.
-pub fn find_include(file: &Path, include: &Path, include_paths: &[PathBuf]) -> Option<PathBuf> {
+/// It also handles includes relative to the `source_roots` (e.g. 'src/main/...').
+pub fn find_include(
+    file: &Path,
+    include: &Path,
+    include_paths: &[PathBuf],
+    source_roots: &[String],
+) -> Option<PathBuf> {
     // Relative to file
     if let Some(parent) = file.parent() {
         let path = parent.join(include);
@@ -41,22 +57,15 @@ pub fn find_include(file: &Path, include: &Path, include_paths: &[PathBuf]) -> O
         }
     }
 
-    // Looking for relative includes
+    // Looking for includes relative to the source roots
     // Also 'src/main/...' is correctly resolved
-    let mut relpath: PathBuf = file
-        .components()
-        .map(|e| e.as_os_str())
-        .skip_while(|&e| e != "include" && e != "src")
-        .skip(1)
-        .skip_while(|&e| e == "main")
-        .collect();
-
-    if !relpath.as_os_str().is_empty() {
-        relpath.pop(); // Remove filename
-        for include_path in include_paths {
-            let path = include_path.join(&relpath).join(&include);
-            if path.exists() {
-                return Some(path);
+    if let Some(dir) = file.parent() {
+        for relpath in source_relpaths(dir, source_roots) {
+            for include_path in include_paths {
+                let path = include_path.join(relpath).join(include);
+                if path.exists() {
+                    return Some(path);
+                }
             }
         }
     }
@@ -117,11 +126,13 @@ mod tests {
 
     #[test]
     fn test_find_include() {
+        let source_roots = ["src".into(), "include".into()];
         assert_eq!(
             find_include(
                 Path::new("tests/src/refs/Main.cpp"),
                 Path::new("Functions.hpp"),
                 &[PathBuf::from("tests/src")],
+                &source_roots,
             ),
             Some(PathBuf::from("tests/src/refs/Functions.hpp"))
         );
@@ -130,8 +141,18 @@ mod tests {
                 Path::new("tests/src/refs/Main.cpp"),
                 Path::new("Unused.hpp"),
                 &[PathBuf::from("tests/src")],
+                &source_roots,
             ),
             Some(PathBuf::from("tests/src/Unused.hpp"))
         );
+        assert_eq!(
+            find_include(
+                Path::new("tests/src/ref/Main.cpp"),
+                Path::new("ExternalRef.hpp"),
+                &[PathBuf::from("tests/include")],
+                &source_roots,
+            ),
+            Some(PathBuf::from("tests/include/ref/ExternalRef.hpp"))
+        );
     }
 }
@@ -0,0 +1,340 @@
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use super::analyze::{self, Analysis, Include, TranslationUnits};
+use super::config::{Config, Settings};
+use super::dependencies::Dependencies;
+use super::edits::Edits;
+use super::error::Error;
+use super::fileio::IncludeStatement;
+use super::report::{FileReport, Propagation, Report};
+
+/// Number of precompiled headers each worker keeps for the files including them
+const CACHED_HEADERS: usize = 4;
+
+/// Number of times a file is analyzed at most,
+/// bounding the re-analyses of files modified by circular includes
+const MAX_VISITS: usize = 3;
+
+/// Walks from the given roots through their dependencies (the files including them),
+/// removing unused includes and propagating them to the dependencies.
+pub struct Walk<'a> {
+    pub config: &'a Config,
+    pub include_paths: &'a [PathBuf],
+    pub index: &'a Dependencies,
+    /// Add includes for used headers that are only included indirectly
+    pub add_missing: bool,
+    /// Replace includes that are only used for incomplete types with forward declarations
+    pub forward_declare: bool,
+}
+
+struct Task<'a> {
+    file: PathBuf,
+    /// Compiler args of the build configurations
+    configurations: &'a [Vec<String>],
+    /// Header whose analysis queued this file, it is precompiled for all files including it
+    header: Option<PathBuf>,
+    root: bool,
+}
+
+/// Analysis of a file
+struct Visit {
+    /// Version of the file after its own edits
+    version: usize,
+    count: usize,
+}
+
+struct Queue<'a> {
+    tasks: VecDeque<Task<'a>>,
+    visited: HashMap<PathBuf, Visit>,
+    /// Number of tasks currently processed
+    active: usize,
+}
+
+impl<'a> Walk<'a> {
+    /// Processes the `roots` with the compiler args of their build configurations
+    /// using `jobs` worker threads.
+    ///
+    /// The dependencies of a file are processed before the next root (depth first).
+    /// The files are analyzed concurrently, but the edits are serialized.
+    /// Files modified after their analysis are analyzed again when they are reached.
+    pub fn run(
+        &self,
+        roots: &[(PathBuf, Vec<Vec<String>>)],
+        edits: Edits,
+        jobs: usize,
+    ) -> (Edits, Report) {
+        let queue = Mutex::new(Queue {
+            tasks: roots
+                .iter()
+                .map(|(file, configurations)| Task {
+                    file: file.clone(),
+                    configurations,
+                    header: None,
+                    root: true,
+                })
+                .collect(),
+            visited: HashMap::new(),
+            active: 0,
+        });
+        let done = Condvar::new();
+        let edits = Mutex::new(edits);
+        let report = Mutex::new(Report::default());
+
+        thread::scope(|scope| {
+            for _ in 0..jobs.max(1) {
+                scope.spawn(|| self.worker(&queue, &done, &edits, &report));
+            }
+        });
+
+        (edits.into_inner().unwrap(), report.into_inner().unwrap())
+    }
+
+    fn worker<'r>(
+        &self,
+        queue: &Mutex<Queue<'r>>,
+        done: &Condvar,
+        edits: &Mutex<Edits>,
+        report: &Mutex<Report>,
+    ) {
+        // Translation units are bound to the thread of their index
+        let index = analyze::create_index();
+        let mut units = TranslationUnits::new(&index, CACHED_HEADERS);
+        loop {
+            let task = {
+                let mut queue = queue.lock().unwrap();
+                loop {
+                    if let Some(task) = queue.tasks.pop_front() {
+                        // Files that were modified after their analysis (e.g. by the propagation
+                        // of a header analyzed concurrently or later) are analyzed again
+                        let version = edits.lock().unwrap().version(&task.file);
+                        let revisit = queue.visited.get(&task.file).is_none_or(|visit| {
+                            visit.version != version && visit.count < MAX_VISITS
+                        });
+                        if revisit {
+                            let visit = queue
+                                .visited
+                                .entry(task.file.clone())
+                                .or_insert(Visit { version, count: 0 });
+                            visit.version = version;
+                            visit.count += 1;
+                            queue.active += 1;
+                            break task;
+                        } else if !task.root && queue.visited[&task.file].version == version {
+                            info!(" -> Circular includes: {}", task.file.to_string_lossy());
+                        }
+                    } else if queue.active == 0 {
+                        return;
+                    } else {
+                        queue = done.wait(queue).unwrap();
+                    }
+                }
+            };
+
+            let (dependencies, version) = self.remove_unused_includes(
+                &task.file,
+                task.configurations,
+                task.header.as_deref(),
+                &mut units,
+                edits,
+                report,
+            );
+
+            let mut queue = queue.lock().unwrap();
+            queue.visited.get_mut(&task.file).unwrap().version = version;
+            // Continue with the dependencies first
+            for dependency in dependencies.iter().rev() {
+                queue.tasks.push_front(Task {
+                    file: dependency.clone(),
+                    configurations: task.configurations,
+                    header: Some(task.file.clone()),
+                    root: false,
+                });
+            }
+            queue.active -= 1;
+            done.notify_all();
+        }
+    }
+
+    /// Removes the includes of the `file` that are unused in all build configurations
+    /// and adds them to its dependencies.
+    ///
+    /// The findings and errors are added to the `report`.
+    /// Returns the dependencies that have to be analyzed next
+    /// and the version of the `file` after its edits.
+    fn remove_unused_includes(
+        &self,
+        file: &Path,
+        configurations: &[Vec<String>],
+        header: Option<&Path>,
+        units: &mut TranslationUnits,
+        edits: &Mutex<Edits>,
+        report: &Mutex<Report>,
+    ) -> (&'a [PathBuf], usize) {
+        info!("Analyzing {}", file.to_string_lossy());
+        let settings = self.config.settings(file);
+        let failed = |err: Error| {
+            error!("{}", err);
+            report.lock().unwrap().errors.push(err);
+        };
+
+        loop {
+            let (version, unsaved, versions) = {
+                let edits = edits.lock().unwrap();
+                (edits.version(file), edits.unsaved(), edits.versions())
+            };
+
+            let mut analysis = match units.analyze_configurations(
+                file,
+                configurations,
+                &settings,
+                &unsaved,
+                header.map(|header| (header, &versions)),
+            ) {
+                Ok(analysis) => analysis,
+                Err(err) => {
+                    failed(err);
+                    return (&[], version);
+                }
+            };
+            let mut includes = analysis.unused.clone();
+            if self.forward_declare {
+                includes.extend(analysis.forward.iter().map(|f| f.include.clone()));
+            }
+
+            let mut edits = edits.lock().unwrap();
+            if edits.version(file) != version {
+                // Modified by another worker, the line numbers are outdated
+                continue;
+            }
+            // Report the lines of the files before all edits
+            let reverted = original_locations(&mut analysis, file, &edits);
+
+            info!(" -> Remove {:?}", includes);
+            if !analysis.missing.is_empty() {
+                info!(" -> Missing {:?}", analysis.missing);
+            }
+            let mut propagated = Vec::new();
+            let dependencies = self.index.get(file);
+
+            // On failure the includes are still there, nothing to propagate
+            let removed = edits
+                .remove_includes(file, &settings, &includes)
+                .map_err(failed)
+                .is_ok();
+            if removed && !includes.is_empty() {
+                for dependency in dependencies {
+                    let settings = self.config.settings(dependency);
+                    // Add removed includes
+                    let includes = includes
+                        .iter()
+                        .map(|i| self.statement(i, dependency, &settings));
+                    match edits.add_includes(dependency, &settings, includes) {
+                        // Only report the includes the dependency did not have yet
+                        Ok(added) => propagated.extend(added.iter().map(|include| Propagation {
+                            dependency: dependency.clone(),
+                            include: include.to_string(),
+                        })),
+                        Err(err) => failed(err),
+                    }
+                }
+            }
+
+            if self.forward_declare && removed && !analysis.forward.is_empty() {
+                let declarations = analysis
+                    .forward
+                    .iter()
+                    .flat_map(|f| f.declarations.iter().cloned());
+                if let Err(err) = edits.add_declarations(file, &settings, declarations) {
+                    failed(err);
+                }
+            }
+
+            if self.add_missing && !analysis.missing.is_empty() {
+                // Spelled for the file, e.g. relative to it
+                let includes = analysis.missing.iter().map(|m| {
+                    settings
+                        .mappings
+                        .public(&m.include.path)
+                        .cloned()
+                        .unwrap_or_else(|| m.statement.clone())
+                });
+                if let Err(err) = edits.add_includes(file, &settings, includes) {
+                    failed(err);
+                }
+            }
+
+            report.lock().unwrap().files.push(FileReport {
+                file: file.into(),
+                unused: analysis.unused,
+                ignored: analysis.ignored,
+                missing: analysis.missing,
+                forward: analysis.forward,
+                associated_not_first: analysis.associated_not_first,
+                propagated,
+                reverted,
+                diagnostics: analysis.diagnostics,
+            });
+            return (dependencies, edits.version(file));
+        }
+    }
+
+    /// Returns the include statement for the `include` in the given `file`.
+    ///
+    /// Private headers are replaced by their public header.
+    fn statement(&self, include: &Include, file: &Path, settings: &Settings) -> IncludeStatement {
+        if let Some(public) = settings.mappings.public(&include.path) {
+            return public.clone();
+        }
+        include
+            .get_local(file, self.include_paths, &settings.source_roots)
+            .map_or_else(
+                || IncludeStatement::Global(include.name.clone()),
+                IncludeStatement::Local,
+            )
+    }
+}
+
+/// Moves the findings of the `analysis` of the `file` to the lines of the original contents.
+///
+/// Findings on lines added by previous edits (e.g. propagated includes)
+/// have no original location and are dropped.
+/// Except for the unused includes, which are still removed and returned separately,
+/// located where they were inserted, like the clang diagnostics on added lines.
+fn original_locations(analysis: &mut Analysis, file: &Path, edits: &Edits) -> Vec<Include> {
+    let lines = edits.line_map(file);
+    let relocate = |include: &mut Include| match lines.get(include.line) {
+        Some(line) => {
+            include.line = line;
+            true
+        }
+        None => false,
+    };
+    let mut reverted = Vec::new();
+    for mut include in mem::take(&mut analysis.unused) {
+        if relocate(&mut include) {
+            analysis.unused.push(include);
+        } else {
+            include.line = lines.position(include.line);
+            reverted.push(include);
+        }
+    }
+    analysis.ignored.retain_mut(|i| relocate(&mut i.include));
+    analysis.missing.retain_mut(|m| relocate(&mut m.include));
+    analysis.forward.retain_mut(|f| relocate(&mut f.include));
+    if let Some(mut include) = analysis.associated_not_first.take() {
+        if relocate(&mut include) {
+            analysis.associated_not_first = Some(include);
+        }
+    }
+
+    for diagnostic in &mut analysis.diagnostics {
+        if let Some(path) = &diagnostic.path {
+            diagnostic.line = edits.line_map(path).position(diagnostic.line);
+        }
+    }
+    reverted
+}
@@ -0,0 +1,12 @@
+#pragma once
+
+namespace outer {
+namespace inner {
+
+class Foo {
+  public:
+    int x;
+};
+
+} // namespace inner
+} // namespace outer
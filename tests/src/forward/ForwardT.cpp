@@ -0,0 +1,7 @@
+#include "Bar.hpp"
+#include "Foo.hpp"
+
+void foo(outer::inner::Foo *foo);
+void foo(const outer::inner::Foo &foo);
+
+int bar(const Bar &bar) { return bar.y; }
@@ -0,0 +1,7 @@
+#pragma once
+
+class Inner {
+  public:
+    Inner() {}
+    ~Inner() {}
+};
@@ -0,0 +1,7 @@
+#include "nested/Nested.hpp"
+
+int main(int argc, char const *argv[]) {
+    Nested nested;
+    Inner inner;
+    return 0;
+}
@@ -0,0 +1,7 @@
+#include "Outer.hpp"
+
+int main(int argc, char const *argv[]) {
+    Outer outer;
+    Inner inner;
+    return 0;
+}
@@ -0,0 +1,8 @@
+#pragma once
+
+#include "../Inner.hpp"
+
+class Nested {
+  public:
+    Inner inner;
+};
@@ -0,0 +1,5 @@
+#pragma once
+
+namespace qual {
+int other();
+} // namespace qual
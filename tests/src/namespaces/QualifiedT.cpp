@@ -0,0 +1,7 @@
+#include "Opens.hpp"
+#include "Type.hpp"
+
+int main(int argc, char const *argv[]) {
+    qual::T t;
+    return 0;
+}
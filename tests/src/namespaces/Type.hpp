@@ -0,0 +1,5 @@
+#pragma once
+
+namespace qual {
+struct T {};
+} // namespace qual
@@ -0,0 +1,9 @@
+#define SHARED_EXTRA
+#include "Shared.hpp"
+#include "Unused.hpp"
+
+int main() {
+    Extra extra;
+    Shared shared;
+    return 0;
+}
@@ -0,0 +1,7 @@
+#include "Shared.hpp"
+#include "Unused.hpp"
+
+int main() {
+    Shared shared;
+    return 0;
+}
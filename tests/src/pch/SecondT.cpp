@@ -0,0 +1,9 @@
+#include "Inner.hpp"
+#include "Shared.hpp"
+#include "Unused.hpp"
+
+int main() {
+    Inner inner;
+    Shared shared;
+    return 0;
+}
@@ -0,0 +1,11 @@
+#pragma once
+
+#include "Inner.hpp"
+
+#ifdef SHARED_EXTRA
+#include "Extra.hpp"
+#endif
+
+struct Shared {
+    Inner inner;
+};
@@ -0,0 +1,4 @@
+#pragma once
+// IWYU pragma: private, include "Public.hpp"
+
+class Detail {};
@@ -0,0 +1,5 @@
+#pragma once
+
+// IWYU pragma: begin_exports
+#include "Exported.hpp"
+// IWYU pragma: end_exports
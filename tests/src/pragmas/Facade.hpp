@@ -0,0 +1,8 @@
+#pragma once
+
+#include "Public.hpp"
+
+class Facade {
+  public:
+    Detail detail;
+};
@@ -0,0 +1,10 @@
+#include "Exporter.hpp"
+#include "Facade.hpp"
+#include "Kept.hpp" // IWYU pragma: keep
+
+int main(int argc, char const *argv[]) {
+    Facade facade;
+    Exported exported;
+    Detail detail;
+    return 0;
+}
@@ -0,0 +1,7 @@
+#pragma once
+
+class AutoType {
+  public:
+    AutoType() {}
+    ~AutoType() {}
+};
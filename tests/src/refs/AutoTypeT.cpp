@@ -0,0 +1,8 @@
+#include "AutoType.hpp"
+#include "AutoTypeDef.hpp"
+
+int main(int argc, char const *argv[]) {
+    auto value = makeAutoType();
+    decltype(makeAutoType()) other = makeAutoType();
+    return 0;
+}
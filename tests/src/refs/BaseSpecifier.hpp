@@ -0,0 +1,6 @@
+#pragma once
+
+class BaseSpecifier {
+  public:
+    int value;
+};
@@ -0,0 +1,7 @@
+#include "BaseSpecifier.hpp"
+#include "BaseSpecifierVar.hpp"
+
+// The base specifier is the only reference to BaseSpecifier.hpp
+class Derived : public decltype(baseSpecifier) {};
+
+int main(int argc, char const *argv[]) { return 0; }
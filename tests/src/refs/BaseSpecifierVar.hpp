@@ -0,0 +1,5 @@
+#pragma once
+
+#include "BaseSpecifier.hpp"
+
+extern BaseSpecifier baseSpecifier;
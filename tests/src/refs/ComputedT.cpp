@@ -0,0 +1,4 @@
+#define COMPUTED_HEADER "Computed.hpp"
+#include COMPUTED_HEADER
+
+int main(int argc, char const *argv[]) { return 0; }
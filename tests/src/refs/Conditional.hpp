@@ -0,0 +1,4 @@
+#pragma once
+
+#define FEATURE_X
+#define VERSION 3
@@ -0,0 +1,9 @@
+#include "Conditional.hpp"
+
+int main(int argc, char const *argv[]) {
+#if defined(FEATURE_X) && VERSION > 2
+    return 1;
+#else
+    return 0;
+#endif
+}
@@ -0,0 +1,6 @@
+#pragma once
+
+class MemberRefExpr {
+  public:
+    int value() const { return 0; }
+};
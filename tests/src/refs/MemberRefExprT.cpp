@@ -0,0 +1,4 @@
+#include "MemberRefExpr.hpp"
+#include "MemberRefExprDef.hpp"
+
+int main(int argc, char const *argv[]) { return getMemberRefExpr().value(); }
@@ -0,0 +1,7 @@
+#pragma once
+
+#include "MemberRef.hpp"
+
+struct MemberRefOuter {
+    MemberRef inner;
+};
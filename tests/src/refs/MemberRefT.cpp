@@ -0,0 +1,7 @@
+#include "MemberRef.hpp"
+#include "MemberRefOuter.hpp"
+
+// The designator is the only reference to MemberRef.hpp
+MemberRefOuter outer = {{.member = 1}};
+
+int main(int argc, char const *argv[]) { return 0; }
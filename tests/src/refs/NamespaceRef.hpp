@@ -0,0 +1,5 @@
+#pragma once
+
+namespace nsref {
+int value();
+} // namespace nsref
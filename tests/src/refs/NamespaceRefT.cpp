@@ -0,0 +1,5 @@
+#include "NamespaceRef.hpp"
+
+using namespace nsref;
+
+int main(int argc, char const *argv[]) { return 0; }
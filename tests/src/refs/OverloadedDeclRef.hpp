@@ -0,0 +1,4 @@
+#pragma once
+
+void overloaded(int value);
+void overloaded(double value);
@@ -0,0 +1,5 @@
+#include "OverloadedDeclRef.hpp"
+
+template <typename T> void call(T value) { overloaded(value); }
+
+int main(int argc, char const *argv[]) { return 0; }
@@ -0,0 +1,8 @@
+#include "stdlib/chrono"
+#include "stdlib/string"
+
+// Both are declared in the same internal header, the symbols map them to their standard header
+std::string text;
+std::chrono::duration<int> timeout;
+
+int main(int argc, char const *argv[]) { return 0; }
@@ -0,0 +1,2 @@
+// Included inside a function body
+int variableRef = 0;
@@ -0,0 +1,6 @@
+int main(int argc, char const *argv[]) {
+#include "VariableRef.hpp"
+    // The capture is the only reference to the variable
+    auto lambda = [variableRef]() { return 0; };
+    return lambda();
+}
@@ -0,0 +1,11 @@
+#pragma once
+#pragma GCC system_header
+
+// Internal header declaring the symbols of multiple standard headers
+namespace std {
+class string {};
+
+namespace chrono {
+template <typename Rep> class duration {};
+} // namespace chrono
+} // namespace std
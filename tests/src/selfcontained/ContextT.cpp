@@ -0,0 +1,7 @@
+#include "Base.hpp"
+#include "Derived.hpp"
+
+int main(int argc, char const *argv[]) {
+    Derived derived;
+    return 0;
+}
@@ -0,0 +1,6 @@
+#pragma once
+
+class Derived {
+  public:
+    Base base;
+};
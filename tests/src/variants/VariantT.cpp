@@ -0,0 +1,8 @@
+#include "Foo.hpp"
+
+int main(int argc, char const *argv[]) {
+#ifdef ENABLE_FOO
+    Foo foo;
+#endif
+    return 0;
+}
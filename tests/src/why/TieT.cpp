@@ -0,0 +1,4 @@
+#include "Unused.hpp"
+#include "Used.hpp"
+
+int main(int argc, char const *argv[]) { return used() + shared(); }
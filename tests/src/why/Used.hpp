@@ -0,0 +1,5 @@
+#pragma once
+
+#include "Shared.hpp"
+
+int used();